@@ -0,0 +1,306 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::*;
+
+// cleared by a restart or a passed measurement, so start_challenge_round knows to start the next round
+#[derive(Resource)]
+pub struct ChallengeRound {
+    pub started: bool,
+}
+
+// the gates generate_new_piece picks from
+pub struct GatePool {
+    pub gates: &'static [(Gate, f32)],
+    pub controls: &'static [Gate],
+    // whether swap, rotation, and reset gates can replace the gates above
+    pub special_gates: bool,
+}
+
+// controls placed by a challenge always point up, hidden blocks are drawn as a question mark so oracles stay secret
+struct FixedBlock {
+    x: i32,
+    y: i32,
+    gate: Gate,
+    hidden: bool,
+}
+
+impl FixedBlock {
+    fn shown(x: i32, y: i32, gate: Gate) -> Self {
+        FixedBlock {
+            x,
+            y,
+            gate,
+            hidden: false,
+        }
+    }
+    fn hidden(x: i32, y: i32, gate: Gate) -> Self {
+        FixedBlock {
+            x,
+            y,
+            gate,
+            hidden: true,
+        }
+    }
+}
+
+impl Challenge {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Challenge::Endless => "Endless",
+            Challenge::Teleportation => "Teleportation",
+            Challenge::DeutschJozsa => "Deutsch-Jozsa",
+            Challenge::Grover => "Grover",
+            Challenge::PhaseKickback => "Phase Kickback",
+            Challenge::Synthesis => "Synthesis",
+        }
+    }
+    pub fn gate_pool(&self) -> GatePool {
+        match self {
+            Challenge::Endless => GatePool {
+                gates: &GATES_WITHOUT_CONTROL,
+                controls: &CONTROL_GATES,
+                special_gates: true,
+            },
+            Challenge::Teleportation => GatePool {
+                gates: &TELEPORTATION_GATES,
+                controls: &[Gate::C, Gate::CC],
+                special_gates: false,
+            },
+            Challenge::DeutschJozsa => GatePool {
+                gates: &DEUTSCH_JOZSA_GATES,
+                controls: &[Gate::C],
+                special_gates: false,
+            },
+            Challenge::Grover => GatePool {
+                gates: &GROVER_GATES,
+                controls: &[Gate::C],
+                special_gates: false,
+            },
+            Challenge::PhaseKickback => GatePool {
+                gates: &PHASE_KICKBACK_GATES,
+                controls: &[Gate::C],
+                special_gates: false,
+            },
+            Challenge::Synthesis => GatePool {
+                gates: &SYNTHESIS_GATES,
+                controls: &[Gate::C],
+                special_gates: false,
+            },
+        }
+    }
+    // the wires the measurement piece has to be placed on, from the bottom up
+    pub fn measure_wires(&self) -> Option<&[i32]> {
+        match self {
+            Challenge::Endless | Challenge::Synthesis => None,
+            Challenge::Teleportation => Some(&[2]),
+            Challenge::DeutschJozsa | Challenge::PhaseKickback => Some(&[0]),
+            Challenge::Grover => Some(&[0, 1]),
+        }
+    }
+    pub fn is_satisfied_by(
+        &self,
+        objective: Objective,
+        board: &mut Board,
+        measure_block_locations: &[(i32, i32)],
+        shots: &mut Shots,
+    ) -> bool {
+        if !self.is_measured_on_its_wires(measure_block_locations) {
+            return false;
+        }
+        if let Some(passed) = shots.sample(objective, board, measure_block_locations) {
+            return passed;
+        }
+        objective.is_satisfied_by(board, measure_block_locations)
+    }
+    // measuring the wrong wires gets no credit at all
+    pub fn grade(
+        &self,
+        objective: Objective,
+        board: &mut Board,
+        measure_block_locations: &[(i32, i32)],
+        shots: &mut Shots,
+    ) -> f64 {
+        if !self.is_measured_on_its_wires(measure_block_locations) {
+            return 0.;
+        }
+        if let Some(grade) = shots.grade(objective, board, measure_block_locations) {
+            return grade;
+        }
+        objective.grade(board, measure_block_locations)
+    }
+    fn is_measured_on_its_wires(&self, measure_block_locations: &[(i32, i32)]) -> bool {
+        if let Some(measure_wires) = self.measure_wires() {
+            measure_block_locations
+                .iter()
+                .map(|&(_, y)| y)
+                .eq(measure_wires.iter().copied())
+        } else {
+            true
+        }
+    }
+    // picks a random round of the challenge, returning its objective and the blocks to place
+    fn new_round(&self) -> (Objective, Vec<FixedBlock>) {
+        let mut rng = rand::thread_rng();
+        match self {
+            Challenge::Endless => (Objective::Measure0, vec![]),
+            // an unknown Ry rotation on wire 0, which the gate pool cannot make on wire 2 directly
+            Challenge::Teleportation => {
+                let angle = *TELEPORT_ANGLES
+                    .choose(&mut rng)
+                    .unwrap_or(&TELEPORT_ANGLES[0]);
+                (
+                    Objective::Teleport(angle),
+                    vec![FixedBlock::hidden(0, 0, Gate::Ry(angle))],
+                )
+            }
+            // wire 0 is the input and wire 1 the ancilla, the padding gates on the hidden oracle can change the ancilla
+            // (X then Z turns |-> into -|+> for oracle 1), which is fine only because it never gets entangled with the input
+            Challenge::DeutschJozsa => {
+                let oracle = rng.gen_range(0..4);
+                (
+                    if oracle < 2 {
+                        Objective::OracleConstant
+                    } else {
+                        Objective::OracleBalanced
+                    },
+                    vec![
+                        FixedBlock::shown(0, 0, Gate::H),
+                        FixedBlock::shown(0, 1, Gate::X),
+                        FixedBlock::shown(1, 1, Gate::H),
+                        FixedBlock::hidden(2, 0, [Gate::X, Gate::X, Gate::C, Gate::AC][oracle]),
+                        FixedBlock::hidden(2, 1, [Gate::Z, Gate::X, Gate::X, Gate::X][oracle]),
+                        FixedBlock::hidden(3, 0, Gate::X),
+                        FixedBlock::hidden(3, 1, Gate::Z),
+                    ],
+                )
+            }
+            // the oracle flips the sign of the marked item with a CZ between X gates on the wires where it is 0
+            Challenge::Grover => {
+                let marked = rng.gen_range(0..4);
+                let mut fixed_blocks = vec![
+                    FixedBlock::shown(0, 0, Gate::H),
+                    FixedBlock::shown(0, 1, Gate::H),
+                    FixedBlock::hidden(2, 0, Gate::C),
+                    FixedBlock::hidden(2, 1, Gate::Z),
+                ];
+                for x in [1, 3] {
+                    for y in 0..2 {
+                        // Z commutes with the CZ, so a pair of them pads the oracle without changing it
+                        let gate = if marked & (1 << y) != 0 {
+                            Gate::Z
+                        } else {
+                            Gate::X
+                        };
+                        fixed_blocks.push(FixedBlock::hidden(x, y, gate));
+                    }
+                }
+                (Objective::FindMarked(marked), fixed_blocks)
+            }
+            // wire 1 is in the eigenstate |1> of the controlled gate, so its phase kicks back onto wire 0
+            Challenge::PhaseKickback => {
+                let gate = *[Gate::Z, Gate::S, Gate::Sdg]
+                    .choose(&mut rng)
+                    .unwrap_or(&Gate::Z);
+                (
+                    Objective::Measure0,
+                    vec![
+                        FixedBlock::shown(0, 0, Gate::H),
+                        FixedBlock::shown(0, 1, Gate::X),
+                        FixedBlock::shown(1, 0, Gate::C),
+                        FixedBlock::shown(1, 1, gate),
+                    ],
+                )
+            }
+            // no fixed blocks, the whole board left of the measurement has to make the operator
+            Challenge::Synthesis => (
+                *SYNTHESIS_OBJECTIVES
+                    .choose(&mut rng)
+                    .unwrap_or(&SYNTHESIS_OBJECTIVES[0]),
+                vec![],
+            ),
+        }
+    }
+}
+
+pub fn cycle_challenge(keys: Res<Input<KeyCode>>, mut challenge: ResMut<Challenge>) {
+    if keys.just_pressed(CYCLE_CHALLENGE_KEYCODE) {
+        *challenge = match CHALLENGES.iter().position(|&other| other == *challenge) {
+            Some(index) => CHALLENGES
+                .get(index + 1)
+                .copied()
+                .unwrap_or(Challenge::Endless),
+            None => CHALLENGES[0],
+        };
+    }
+}
+
+// sets the objective of a new round and places its fixed blocks
+pub fn start_challenge_round(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    challenge: Res<Challenge>,
+    mut round: ResMut<ChallengeRound>,
+    mut objective: ResMut<Objective>,
+) {
+    if *challenge == Challenge::Endless || round.started {
+        return;
+    }
+    round.started = true;
+    let (new_objective, fixed_blocks) = challenge.new_round();
+    *objective = new_objective;
+    for fixed_block in fixed_blocks {
+        let block = Block {
+            x: fixed_block.x,
+            y: fixed_block.y,
+            gate: fixed_block.gate,
+        };
+        if fixed_block.hidden {
+            spawn_hidden_block(&mut commands, block);
+        } else {
+            spawn_block(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                block,
+                FIXED_BLOCK_COLOR,
+                false,
+            );
+        }
+    }
+}
+
+fn spawn_hidden_block(commands: &mut Commands, block: Block) -> Entity {
+    let gate = block.gate;
+    let mut x = commands.spawn((
+        block,
+        SpriteBundle {
+            sprite: Sprite {
+                color: HIDDEN_BLOCK_COLOR,
+                custom_size: Some(Vec2::new(OPERATOR_SIZE, OPERATOR_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., 1.),
+            ..default()
+        },
+    ));
+    if CONTROL_GATES.contains(&gate) || gate == Gate::CC {
+        x.insert(Control { on_top: false });
+    }
+    x.with_children(|parent| {
+        parent.spawn(Text2dBundle {
+            text: Text::from_section(
+                "?",
+                TextStyle {
+                    font_size: OPERATOR_FONT_SIZE,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            transform: Transform::from_xyz(0., 0., 1.),
+            ..default()
+        });
+    });
+    x.id()
+}
@@ -30,16 +30,28 @@ pub enum Shape {
     M,
 }
 impl Shape {
-    pub fn control_on_top(&self, rotation: i32) -> bool {
-        match self {
-            Shape::J => [false, true, true, false][rotation as usize],
-            Shape::L => [false, false, true, true][rotation as usize],
-            Shape::O => [true, true, false, false][rotation as usize],
-            Shape::S => [false, false, true, true][rotation as usize],
-            Shape::T => [false, true, true, true][rotation as usize],
-            Shape::Z => [true, false, false, true][rotation as usize],
-            Shape::I | Shape::M => true,
+    // a control points down if it reaches an operator of the piece by walking down through the piece's other controls, and up otherwise
+    pub fn control_on_top(&self, number: i32, rotation: i32, control_numbers: &[i32]) -> bool {
+        let (x, y) = self.rotation_location(number, rotation);
+        let number_at = |y: i32| {
+            (0..4)
+                .find(|&other| self.rotation_location(other, rotation) == (x, y) && other != number)
+        };
+        let mut below = y - 1;
+        while let Some(other) = number_at(below) {
+            if !control_numbers.contains(&other) {
+                return true;
+            }
+            below -= 1;
+        }
+        let mut above = y + 1;
+        while let Some(other) = number_at(above) {
+            if !control_numbers.contains(&other) {
+                return false;
+            }
+            above += 1;
         }
+        true
     }
     // for O, S, and Z there are 4, 2, and 2 possilbe spawn locations, but we just give one option
     pub fn can_control_spawn(&self, number: i32) -> bool {
@@ -53,6 +65,22 @@ impl Shape {
             Shape::I | Shape::M => false,
         }
     }
+    // a second control on the far end of J and L, which turns the gate between them into a Toffoli when the piece is upright
+    pub fn can_extra_control_spawn(&self, number: i32) -> bool {
+        match self {
+            Shape::J => [false, false, false, true][number as usize],
+            Shape::L => [true, false, false, false][number as usize],
+            _ => false,
+        }
+    }
+    // the block directly above or below, used to pair up the two halves of a swap gate
+    pub fn vertical_neighbour(&self, number: i32, rotation: i32) -> Option<i32> {
+        let (x, y) = self.rotation_location(number, rotation);
+        (0..4).find(|&other| {
+            let (other_x, other_y) = self.rotation_location(other, rotation);
+            other_x == x && (other_y - y).abs() == 1
+        })
+    }
     // from https://tetris.fandom.com/wiki/SRS#Pro
     pub fn rotation_location(&self, number: i32, rotation: i32) -> (i32, i32) {
         match self {
@@ -178,17 +206,87 @@ pub enum Gate {
     Z,
     H,
     S,
+    Sdg,
     T,
+    Tdg,
     C,
     AC,
-    M,
+    M(Basis),
+    // mid-circuit measurement, collapses its wire and records the outcome as a classical bit
+    Measure,
+    // classical control, links to an operator like C but reads the last Measure outcome on its wire
+    CC,
+    // resets its wire to |0>, measuring it like Measure and flipping it back when the outcome is 1
+    Reset,
+    // resets its wire like Reset, then turns it into |+>
+    PreparePlus,
+    Swap,
+    ISwap,
+    SqrtSwap,
+    Rx(Angle),
+    Ry(Angle),
+    Rz(Angle),
 }
 impl fmt::Display for Gate {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            Gate::Swap => write!(f, ""),
+            Gate::ISwap => write!(f, "i"),
+            Gate::SqrtSwap => write!(f, "1/2"),
+            Gate::Rx(_) => write!(f, "Rx"),
+            Gate::Ry(_) => write!(f, "Ry"),
+            Gate::Rz(_) => write!(f, "Rz"),
+            Gate::Measure => write!(f, "M"),
+            Gate::Reset => write!(f, "|0>"),
+            Gate::PreparePlus => write!(f, "|+>"),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
+
+// a rational multiple of pi, so gates carrying it stay Eq
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Angle {
+    pub numerator: i32,
+    pub denominator: i32,
+}
+impl Angle {
+    pub const fn new(numerator: i32, denominator: i32) -> Self {
+        Angle {
+            numerator,
+            denominator,
+        }
+    }
+    pub fn radians(&self) -> f64 {
+        std::f64::consts::PI * self.numerator as f64 / self.denominator as f64
+    }
+}
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.numerator, self.denominator) {
+            (1, 1) => write!(f, "pi"),
+            (numerator, 1) => write!(f, "{}pi", numerator),
+            (1, denominator) => write!(f, "pi/{}", denominator),
+            (numerator, denominator) => write!(f, "{}pi/{}", numerator, denominator),
+        }
+    }
+}
+
 impl Gate {
+    // the state a reset or preparation gate leaves its wire in
+    pub fn preparation(&self) -> Option<InitialState> {
+        match self {
+            Gate::Reset => Some(InitialState::Zero),
+            Gate::PreparePlus => Some(InitialState::Plus),
+            _ => None,
+        }
+    }
+    pub fn angle(&self) -> Option<Angle> {
+        match *self {
+            Gate::Rx(angle) | Gate::Ry(angle) | Gate::Rz(angle) => Some(angle),
+            _ => None,
+        }
+    }
     pub fn operator(&self) -> Option<Matrix2<Complex<f64>>> {
         match self {
             Gate::X => Some(Matrix2::new(
@@ -221,24 +319,250 @@ impl Gate {
                 Complex::new(0., 0.),
                 Complex::new(0., 1.),
             )),
+            Gate::Sdg => Some(Matrix2::new(
+                Complex::new(1., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., -1.),
+            )),
             Gate::T => Some(Matrix2::new(
                 Complex::new(1., 0.),
                 Complex::new(0., 0.),
                 Complex::new(0., 0.),
                 Complex::new(1. / (2.).sqrt(), 1. / (2.).sqrt()),
             )),
-            Gate::M => Some(Matrix2::new(
+            Gate::Tdg => Some(Matrix2::new(
                 Complex::new(1., 0.),
                 Complex::new(0., 0.),
                 Complex::new(0., 0.),
+                Complex::new(1. / (2.).sqrt(), -1. / (2.).sqrt()),
+            )),
+            Gate::Rx(angle) => Some(Matrix2::new(
+                Complex::new((angle.radians() / 2.).cos(), 0.),
+                Complex::new(0., -(angle.radians() / 2.).sin()),
+                Complex::new(0., -(angle.radians() / 2.).sin()),
+                Complex::new((angle.radians() / 2.).cos(), 0.),
+            )),
+            Gate::Ry(angle) => Some(Matrix2::new(
+                Complex::new((angle.radians() / 2.).cos(), 0.),
+                Complex::new(-(angle.radians() / 2.).sin(), 0.),
+                Complex::new((angle.radians() / 2.).sin(), 0.),
+                Complex::new((angle.radians() / 2.).cos(), 0.),
+            )),
+            Gate::Rz(angle) => Some(Matrix2::new(
+                Complex::new((angle.radians() / 2.).cos(), -(angle.radians() / 2.).sin()),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new((angle.radians() / 2.).cos(), (angle.radians() / 2.).sin()),
+            )),
+            _ => None,
+        }
+    }
+    // acts on the two halves of a swap gate, these are all symmetric so the order of the two wires does not matter
+    pub fn two_qubit_operator(&self) -> Option<Matrix4<Complex<f64>>> {
+        match self {
+            Gate::Swap => Some(Matrix4::new(
+                Complex::new(1., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(1., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(1., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(1., 0.),
+            )),
+            Gate::ISwap => Some(Matrix4::new(
+                Complex::new(1., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 1.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 1.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(1., 0.),
+            )),
+            Gate::SqrtSwap => Some(Matrix4::new(
+                Complex::new(1., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0.5, 0.5),
+                Complex::new(0.5, -0.5),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0.5, -0.5),
+                Complex::new(0.5, 0.5),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
+                Complex::new(0., 0.),
                 Complex::new(1., 0.),
             )),
             _ => None,
         }
     }
 }
-pub const GATES_WITHOUT_CONTROL: [Gate; 4] = [Gate::X, Gate::Y, Gate::Z, Gate::H];
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Basis {
+    Z,
+    X,
+    Y,
+}
+impl Basis {
+    // the rotation that maps the basis' |0> and |1> states onto the computational ones
+    pub fn change_to_z_basis(&self) -> Matrix2<Complex<f64>> {
+        match self {
+            Basis::Z => Matrix2::identity(),
+            Basis::X => Gate::H.operator().unwrap(),
+            Basis::Y => Gate::H.operator().unwrap() * Gate::Sdg.operator().unwrap(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InitialState {
+    Zero,
+    One,
+    Plus,
+    Minus,
+    PlusI,
+}
+impl InitialState {
+    // the amplitudes of |0> and |1>
+    pub fn amplitudes(&self) -> (Complex<f64>, Complex<f64>) {
+        let half = Complex::new(1. / (2.).sqrt(), 0.);
+        match self {
+            InitialState::Zero => (Complex::new(1., 0.), Complex::new(0., 0.)),
+            InitialState::One => (Complex::new(0., 0.), Complex::new(1., 0.)),
+            InitialState::Plus => (half, half),
+            InitialState::Minus => (half, -half),
+            InitialState::PlusI => (half, Complex::new(0., 1. / (2.).sqrt())),
+        }
+    }
+    // the Clifford gates that make the state from |0>, so the stabilizer backend can start from it too
+    pub fn preparation(&self) -> &[Gate] {
+        match self {
+            InitialState::Zero => &[],
+            InitialState::One => &[Gate::X],
+            InitialState::Plus => &[Gate::H],
+            InitialState::Minus => &[Gate::X, Gate::H],
+            InitialState::PlusI => &[Gate::H, Gate::S],
+        }
+    }
+    pub fn get_name(&self) -> &str {
+        match self {
+            InitialState::Zero => "|0>",
+            InitialState::One => "|1>",
+            InitialState::Plus => "|+>",
+            InitialState::Minus => "|->",
+            InitialState::PlusI => "|i>",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Noise {
+    Depolarizing(f64),
+    BitFlip(f64),
+    PhaseFlip(f64),
+    AmplitudeDamping(f64),
+}
+impl Noise {
+    pub fn kraus_operators(&self) -> Vec<Matrix2<Complex<f64>>> {
+        let scaled = |gate: Gate, probability: f64| {
+            gate.operator().unwrap() * Complex::new(probability.sqrt(), 0.)
+        };
+        match *self {
+            Noise::Depolarizing(p) => vec![
+                Matrix2::identity() * Complex::new((1. - 3. * p / 4.).sqrt(), 0.),
+                scaled(Gate::X, p / 4.),
+                scaled(Gate::Y, p / 4.),
+                scaled(Gate::Z, p / 4.),
+            ],
+            Noise::BitFlip(p) => vec![
+                Matrix2::identity() * Complex::new((1. - p).sqrt(), 0.),
+                scaled(Gate::X, p),
+            ],
+            Noise::PhaseFlip(p) => vec![
+                Matrix2::identity() * Complex::new((1. - p).sqrt(), 0.),
+                scaled(Gate::Z, p),
+            ],
+            Noise::AmplitudeDamping(gamma) => vec![
+                Matrix2::new(
+                    Complex::new(1., 0.),
+                    Complex::new(0., 0.),
+                    Complex::new(0., 0.),
+                    Complex::new((1. - gamma).sqrt(), 0.),
+                ),
+                Matrix2::new(
+                    Complex::new(0., 0.),
+                    Complex::new(gamma.sqrt(), 0.),
+                    Complex::new(0., 0.),
+                    Complex::new(0., 0.),
+                ),
+            ],
+        }
+    }
+    pub fn get_name(&self) -> String {
+        match self {
+            Noise::Depolarizing(p) => format!("Depolarizing {}%", p * 100.),
+            Noise::BitFlip(p) => format!("Bit Flip {}%", p * 100.),
+            Noise::PhaseFlip(p) => format!("Phase Flip {}%", p * 100.),
+            Noise::AmplitudeDamping(gamma) => format!("Amplitude Damping {}%", gamma * 100.),
+        }
+    }
+}
+
+// each gate is picked with probability proportional to its weight
+pub const GATES_WITHOUT_CONTROL: [(Gate, f32); 8] = [
+    (Gate::X, 3.),
+    (Gate::Y, 3.),
+    (Gate::Z, 3.),
+    (Gate::H, 3.),
+    (Gate::S, 1.),
+    (Gate::Sdg, 1.),
+    (Gate::T, 1.),
+    (Gate::Tdg, 1.),
+];
+// the angles rotation gates are generated with, e.g. Ry(pi/3) turns |0> into a 75/25 split
+pub const ROTATION_ANGLES: [Angle; 4] = [
+    Angle::new(1, 4),
+    Angle::new(1, 3),
+    Angle::new(1, 2),
+    Angle::new(2, 3),
+];
 pub const CONTROL_GATES: [Gate; 2] = [Gate::C, Gate::AC];
+pub const RESET_GATES: [Gate; 2] = [Gate::Reset, Gate::PreparePlus];
+pub const SWAP_GATES: [Gate; 3] = [Gate::Swap, Gate::ISwap, Gate::SqrtSwap];
+// gates the stabilizer backend can simulate alone or with a single control, anything else falls back to the dense simulator
+pub const CLIFFORD_GATES: [Gate; 8] = [
+    Gate::X,
+    Gate::Y,
+    Gate::Z,
+    Gate::H,
+    Gate::S,
+    Gate::Sdg,
+    Gate::Swap,
+    Gate::ISwap,
+];
+pub const CONTROLLED_CLIFFORD_GATES: [Gate; 3] = [Gate::X, Gate::Y, Gate::Z];
 
 pub const FASTER_FALL_KEYCODE: KeyCode = KeyCode::Left;
 pub const PIECE_UP_KEYCODE: KeyCode = KeyCode::Up;
@@ -246,33 +570,378 @@ pub const PIECE_DOWN_KEYCODE: KeyCode = KeyCode::Down;
 pub const DROP_PIECE_KEYCODE: KeyCode = KeyCode::Right;
 pub const ROTATE_PIECE_CLOCKWISE: KeyCode = KeyCode::X;
 pub const ROTATE_PIECE_COUNTERCLOCKWISE: KeyCode = KeyCode::Z;
+pub const CYCLE_NOISE_KEYCODE: KeyCode = KeyCode::N;
+pub const CYCLE_CHALLENGE_KEYCODE: KeyCode = KeyCode::C;
+pub const CYCLE_DIFFICULTY_KEYCODE: KeyCode = KeyCode::D;
+pub const CYCLE_SHOTS_KEYCODE: KeyCode = KeyCode::S;
+pub const CYCLE_SCORING_KEYCODE: KeyCode = KeyCode::G;
+pub const CYCLE_LIVES_KEYCODE: KeyCode = KeyCode::L;
+pub const CYCLE_INPUTS_KEYCODE: KeyCode = KeyCode::I;
+pub const SELECT_MEASUREMENT_KEYCODE: KeyCode = KeyCode::Tab;
+// held while moving up or down to move only the selected block of a measurement piece
+pub const SPREAD_MEASUREMENT_KEYCODE: KeyCode = KeyCode::ShiftLeft;
+
+// the states random inputs are picked from
+pub const INITIAL_STATES: [InitialState; 5] = [
+    InitialState::Zero,
+    InitialState::One,
+    InitialState::Plus,
+    InitialState::Minus,
+    InitialState::PlusI,
+];
+pub const INPUTS: [Inputs; 3] = [
+    Inputs::Zero,
+    Inputs::RandomPerGame,
+    Inputs::RandomPerObjective,
+];
+
+// the noisy modes cycled through after the noiseless one
+pub const NOISE_MODES: [Noise; 4] = [
+    Noise::Depolarizing(0.02),
+    Noise::BitFlip(0.02),
+    Noise::PhaseFlip(0.02),
+    Noise::AmplitudeDamping(0.02),
+];
+
+// the shot counts cycled through after exact probabilities
+pub const SHOT_COUNTS: [i32; 3] = [20, 100, 1000];
+// standard normal quantile for the 1% significance level the shot histograms are tested at
+pub const CHI_SQUARED_Z: f64 = 2.326;
+
+// points for every block cleared by a measurement, scaled by its grade in graded scoring
+pub const MEASUREMENT_POINTS: i32 = 10;
+// graded measurements below this grade are poor matches, which fail like wrong measurements do
+pub const GRADED_PASS_THRESHOLD: f64 = 0.8;
+// the starting lives cycled through, the first one is the default
+pub const STARTING_LIVES: [StartingLives; 3] =
+    [StartingLives(3), StartingLives(5), StartingLives(1)];
 
 pub const CONTROL_GATE_CHANCE: f32 = 1.0;
+pub const EXTRA_CONTROL_GATE_CHANCE: f32 = 0.25;
+// replaces the control and the block it points to at spawn with the two halves of a swap gate
+pub const SWAP_GATE_CHANCE: f32 = 0.2;
+// replaces a gate from GATES_WITHOUT_CONTROL with an Rx, Ry, or Rz gate
+pub const ROTATION_GATE_CHANCE: f32 = 0.2;
+// the chance of any other gate being replaced with a reset or preparation gate, which are rare power-ups
+pub const RESET_GATE_CHANCE: f32 = 0.03;
 
 pub const OBJECTIVE_PERIOD: i32 = 10;
 
-pub const OBJECTIVES: [Objective; 4] = [
+// in endless mode, the next objective is generated from a random circuit instead of picked from OBJECTIVES
+pub const GENERATED_OBJECTIVE_CHANCE: f32 = 0.5;
+// adds a control or anticontrol to a gate of a generated circuit
+pub const GENERATED_CONTROL_CHANCE: f32 = 0.5;
+pub const DIFFICULTIES: [Difficulty; 3] = [
+    Difficulty {
+        depth: 2,
+        measure_count: 1,
+    },
+    Difficulty {
+        depth: 4,
+        measure_count: 2,
+    },
+    Difficulty {
+        depth: 6,
+        measure_count: 3,
+    },
+];
+
+// the challenges cycled through after endless mode
+pub const CHALLENGES: [Challenge; 5] = [
+    Challenge::Teleportation,
+    Challenge::DeutschJozsa,
+    Challenge::Grover,
+    Challenge::PhaseKickback,
+    Challenge::Synthesis,
+];
+// the hidden Ry angles of teleportation rounds, leaving out pi/2 since Ry(pi/2)|0> is H|0>, which wire 2 could just make itself
+pub const TELEPORT_ANGLES: [Angle; 3] = [Angle::new(1, 4), Angle::new(1, 3), Angle::new(2, 3)];
+pub const TELEPORTATION_GATES: [(Gate, f32); 4] = [
+    (Gate::H, 1.),
+    (Gate::X, 1.),
+    (Gate::Z, 1.),
+    (Gate::Measure, 1.),
+];
+pub const DEUTSCH_JOZSA_GATES: [(Gate, f32); 3] = [(Gate::H, 2.), (Gate::X, 1.), (Gate::Z, 1.)];
+pub const GROVER_GATES: [(Gate, f32); 3] = [(Gate::H, 2.), (Gate::X, 1.), (Gate::Z, 1.)];
+pub const PHASE_KICKBACK_GATES: [(Gate, f32); 4] =
+    [(Gate::H, 2.), (Gate::S, 1.), (Gate::Sdg, 1.), (Gate::X, 1.)];
+// without swap gates, so SWAP has to be built from CNOTs and S from two Ts
+pub const SYNTHESIS_GATES: [(Gate, f32); 4] =
+    [(Gate::H, 2.), (Gate::X, 1.), (Gate::Z, 1.), (Gate::T, 1.)];
+pub const SYNTHESIS_OBJECTIVES: [Objective; 4] = [
+    Objective::BuildCZ,
+    Objective::BuildSwap,
+    Objective::BuildReversedCNOT,
+    Objective::BuildS,
+];
+
+pub const OBJECTIVES: [Objective; 28] = [
     Objective::Measure0,
     Objective::Measure1,
     Objective::MeasurePhi,
     Objective::MeasurePsi,
+    Objective::MeasureHTH,
+    Objective::MeasureHSTH,
+    Objective::MeasurePlus,
+    Objective::MeasureMinus,
+    Objective::MeasurePlusI,
+    Objective::MeasureMinusI,
+    Objective::PreparePhiPlus,
+    Objective::PreparePhiMinus,
+    Objective::PreparePsiPlus,
+    Objective::PreparePsiMinus,
+    Objective::MeasureGHZ3,
+    Objective::MeasureGHZ4,
+    Objective::MeasureW4,
+    Objective::MeasureUniform3,
+    Objective::Measure101,
+    Objective::Measure0110,
+    Objective::MeasureOneQuarter,
+    Objective::MeasureUnevenPhi,
+    Objective::MeasureControlledRy,
+    Objective::Expectation {
+        basis: Basis::Z,
+        wire_count: 2,
+        value: -1,
+    },
+    Objective::Expectation {
+        basis: Basis::X,
+        wire_count: 1,
+        value: 0,
+    },
+    Objective::Expectation {
+        basis: Basis::X,
+        wire_count: 2,
+        value: 1,
+    },
+    Objective::Expectation {
+        basis: Basis::Z,
+        wire_count: 3,
+        value: -1,
+    },
+    Objective::Expectation {
+        basis: Basis::Y,
+        wire_count: 1,
+        value: -1,
+    },
 ];
 
 impl Objective {
     pub fn measure_count(&self) -> i32 {
         match self {
-            Objective::Measure0 | Objective::Measure1 => 1,
-            Objective::MeasurePhi | Objective::MeasurePsi => 2,
+            Objective::Measure0
+            | Objective::Measure1
+            | Objective::MeasureHTH
+            | Objective::MeasureHSTH
+            | Objective::MeasurePlus
+            | Objective::MeasureMinus
+            | Objective::MeasurePlusI
+            | Objective::MeasureMinusI
+            | Objective::Teleport(_)
+            | Objective::OracleConstant
+            | Objective::OracleBalanced
+            | Objective::BuildS
+            | Objective::MeasureOneQuarter => 1,
+            Objective::MeasurePhi
+            | Objective::MeasurePsi
+            | Objective::PreparePhiPlus
+            | Objective::PreparePhiMinus
+            | Objective::PreparePsiPlus
+            | Objective::PreparePsiMinus
+            | Objective::FindMarked(_)
+            | Objective::BuildCZ
+            | Objective::BuildSwap
+            | Objective::BuildReversedCNOT
+            | Objective::MeasureUnevenPhi
+            | Objective::MeasureControlledRy => 2,
+            Objective::MeasureGHZ3 | Objective::MeasureUniform3 | Objective::Measure101 => 3,
+            Objective::MeasureGHZ4 | Objective::MeasureW4 | Objective::Measure0110 => 4,
+            Objective::Generated { measure_count, .. } => *measure_count,
+            Objective::Expectation { wire_count, .. } => *wire_count,
+        }
+    }
+    // the state the measured wires have to be in, for objectives where the relative phase counts
+    pub fn target_state(&self) -> Option<DVector<Complex<f64>>> {
+        let amplitude = |sign: f64| Complex::new(sign / (2.).sqrt(), 0.);
+        let zero = Complex::new(0., 0.);
+        match self {
+            Objective::PreparePhiPlus => Some(dvector![amplitude(1.), zero, zero, amplitude(1.)]),
+            Objective::PreparePhiMinus => Some(dvector![amplitude(1.), zero, zero, amplitude(-1.)]),
+            Objective::PreparePsiPlus => Some(dvector![zero, amplitude(1.), amplitude(1.), zero]),
+            Objective::PreparePsiMinus => Some(dvector![zero, amplitude(1.), amplitude(-1.), zero]),
+            // the state Ry(angle) makes from |0>
+            Objective::Teleport(angle) => Some(dvector![
+                Complex::new((angle.radians() / 2.).cos(), 0.),
+                Complex::new((angle.radians() / 2.).sin(), 0.)
+            ]),
+            _ => None,
+        }
+    }
+    // the operator every column up to the measurement has to make together, with bit i of the indices on the i-th measured wire
+    pub fn target_operator(&self) -> Option<DMatrix<Complex<f64>>> {
+        let one = Complex::new(1., 0.);
+        let permutation = |permutation: [usize; 4]| {
+            DMatrix::from_fn(4, 4, |row, column| {
+                if permutation[column] == row {
+                    one
+                } else {
+                    Complex::new(0., 0.)
+                }
+            })
+        };
+        match self {
+            Objective::BuildCZ => Some(DMatrix::from_diagonal(&dvector![one, one, one, -one])),
+            Objective::BuildSwap => Some(permutation([0, 2, 1, 3])),
+            // controlled by the second measured wire and targeting the first
+            Objective::BuildReversedCNOT => Some(permutation([0, 1, 3, 2])),
+            Objective::BuildS => Some(DMatrix::from_diagonal(&dvector![one, Complex::new(0., 1.)])),
+            _ => None,
+        }
+    }
+    pub fn is_satisfied_by(
+        &self,
+        board: &mut Board,
+        measure_block_locations: &[(i32, i32)],
+    ) -> bool {
+        if let Some(target_operator) = self.target_operator() {
+            board.implements_operator(&target_operator, measure_block_locations)
+        } else if let Some(target_state) = self.target_state() {
+            board.fidelity_of_column(&target_state, measure_block_locations) > FIDELITY_THRESHOLD
+        } else if let Objective::Expectation { value, .. } = self {
+            (board.expectation_of_column(measure_block_locations) - *value as f64).abs()
+                < self.tolerance()
+        } else if let Some(desired_state) = self.get_desired_state() {
+            board.satisfies_objective(desired_state, measure_block_locations, self.tolerance())
+        } else {
+            false
+        }
+    }
+    // how close the measured wires are to the objective, from 0 when they have nothing in common to 1 when they match
+    pub fn grade(&self, board: &mut Board, measure_block_locations: &[(i32, i32)]) -> f64 {
+        if let Some(target_operator) = self.target_operator() {
+            if board.implements_operator(&target_operator, measure_block_locations) {
+                1.
+            } else {
+                0.
+            }
+        } else if let Some(target_state) = self.target_state() {
+            board.fidelity_of_column(&target_state, measure_block_locations)
+        } else if let Objective::Expectation { value, .. } = self {
+            1. - (board.expectation_of_column(measure_block_locations) - *value as f64).abs() / 2.
+        } else if let Some(desired_state) = self.get_desired_state() {
+            1. - total_variation_distance(
+                &board.partial_state_of_column(measure_block_locations),
+                &desired_state,
+            )
+        } else {
+            0.
+        }
+    }
+    // how far each measured probability may be from the desired one
+    pub fn tolerance(&self) -> f64 {
+        match self {
+            Objective::MeasureOneQuarter
+            | Objective::MeasureUnevenPhi
+            | Objective::MeasureControlledRy => PROBABILITY_TOLERANCE,
+            _ => TOLERANCE,
         }
     }
-    pub fn get_desired_state(&self) -> DVector<f64> {
+    pub fn basis(&self) -> Basis {
         match self {
-            Objective::Measure0 => dvector![1., 0.],
-            Objective::Measure1 => dvector![0., 1.],
-            Objective::MeasurePhi => dvector![1. / 2., 0., 0., 1. / 2.],
-            Objective::MeasurePsi => dvector![0., 1. / 2., 1. / 2., 0.],
+            Objective::MeasurePlus | Objective::MeasureMinus => Basis::X,
+            Objective::MeasurePlusI | Objective::MeasureMinusI => Basis::Y,
+            Objective::Expectation { basis, .. } => *basis,
+            _ => Basis::Z,
         }
     }
+    // probabilities in the objective's basis, or None for objectives that only fix an expectation value
+    pub fn get_desired_state(&self) -> Option<DVector<f64>> {
+        Some(match self {
+            Objective::Measure0
+            | Objective::MeasurePlus
+            | Objective::MeasurePlusI
+            | Objective::OracleConstant => {
+                dvector![1., 0.]
+            }
+            Objective::Measure1
+            | Objective::MeasureMinus
+            | Objective::MeasureMinusI
+            | Objective::OracleBalanced => {
+                dvector![0., 1.]
+            }
+            Objective::MeasurePhi | Objective::PreparePhiPlus | Objective::PreparePhiMinus => {
+                dvector![1. / 2., 0., 0., 1. / 2.]
+            }
+            Objective::MeasurePsi | Objective::PreparePsiPlus | Objective::PreparePsiMinus => {
+                dvector![0., 1. / 2., 1. / 2., 0.]
+            }
+            // a pi/4 relative phase between two Hadamards, which only T (or a rotation) can make
+            Objective::MeasureHTH => {
+                dvector![(2. + (2.).sqrt()) / 4., (2. - (2.).sqrt()) / 4.]
+            }
+            // a 3pi/4 relative phase, from S and T together
+            Objective::MeasureHSTH => {
+                dvector![(2. - (2.).sqrt()) / 4., (2. + (2.).sqrt()) / 4.]
+            }
+            Objective::Teleport(angle) => dvector![
+                (angle.radians() / 2.).cos().powi(2),
+                (angle.radians() / 2.).sin().powi(2)
+            ],
+            Objective::MeasureGHZ3 => uniform_over(8, &[0, 7]),
+            Objective::MeasureGHZ4 => uniform_over(16, &[0, 15]),
+            // a single 1 spread evenly over the four wires, the three wire W state needs amplitudes of 1/sqrt(3) which no gate here can make
+            Objective::MeasureW4 => uniform_over(16, &[1, 2, 4, 8]),
+            Objective::MeasureUniform3 => uniform_over(8, &[0, 1, 2, 3, 4, 5, 6, 7]),
+            Objective::Measure101 => uniform_over(8, &[5]),
+            Objective::Measure0110 => uniform_over(16, &[6]),
+            // from Ry(pi/3)
+            Objective::MeasureOneQuarter => dvector![3. / 4., 1. / 4.],
+            // from Ry(2pi/3) and a CNOT
+            Objective::MeasureUnevenPhi => dvector![1. / 4., 0., 0., 3. / 4.],
+            // from H on wire 0 and a Ry(pi/2) on wire 1 controlled by it
+            Objective::MeasureControlledRy => dvector![1. / 2., 1. / 4., 0., 1. / 4.],
+            Objective::FindMarked(index) => uniform_over(4, &[*index as usize]),
+            Objective::Generated {
+                seed,
+                depth,
+                measure_count,
+            } => {
+                let (mut board, measure_block_locations) =
+                    generated_circuit(*seed, *depth, *measure_count);
+                board.partial_state_of_column(&measure_block_locations)
+            }
+            Objective::Expectation { .. }
+            | Objective::BuildCZ
+            | Objective::BuildSwap
+            | Objective::BuildReversedCNOT
+            | Objective::BuildS => return None,
+        })
+    }
+}
+
+// writes the entries of the target operators, which are all 0, 1, -1, i, or -i
+fn format_entry(entry: Complex<f64>) -> String {
+    if entry.im == 0. {
+        format!("{}", entry.re)
+    } else if entry.re == 0. && entry.im.abs() == 1. {
+        format!("{}i", if entry.im < 0. { "-" } else { "" })
+    } else if entry.re == 0. {
+        format!("{}i", entry.im)
+    } else {
+        format!("{}", entry)
+    }
+}
+
+// equal probabilities on the given outcomes of a measurement with size outcomes
+fn uniform_over(size: usize, outcomes: &[usize]) -> DVector<f64> {
+    DVector::from_fn(size, |i, _| {
+        if outcomes.contains(&i) {
+            1. / outcomes.len() as f64
+        } else {
+            0.
+        }
+    })
 }
 
 pub const OBJECTIVE_FONT_SIZE: f32 = 80.;
@@ -282,8 +951,21 @@ pub const CONTROL_OUTER_RADIUS: f32 = 16.;
 pub const CONTROL_INNER_RADIUS: f32 = 12.;
 pub const OPERATOR_SIZE: f32 = 64.;
 pub const OPERATOR_FONT_SIZE: f32 = 48.;
+// for three letter gate names like Sdg
+pub const LONG_OPERATOR_FONT_SIZE: f32 = 32.;
+pub const ANGLE_FONT_SIZE: f32 = 20.;
+pub const BASIS_FONT_SIZE: f32 = 32.;
+pub const SWAP_SIZE: f32 = 48.;
+pub const SWAP_STROKE_WIDTH: f32 = 8.;
+pub const SWAP_FONT_SIZE: f32 = 24.;
+
+pub const SELECTED_MEASUREMENT_COLOR: Color = Color::rgb(0.6, 0.8, 1.);
+pub const FIXED_BLOCK_COLOR: Color = Color::rgb(0.75, 0.75, 0.75);
+pub const HIDDEN_BLOCK_COLOR: Color = Color::rgb(0.4, 0.4, 0.4);
 
 pub const INITIAL_STATE_DISTANCE_FROM_RIGHT: f32 = 48.;
+// for the inputs other than |0>, which has its own image
+pub const INITIAL_STATE_FONT_SIZE: f32 = 40.;
 
 pub const GAME_OVER_LARGE_FONT_SIZE: f32 = 192.;
 pub const GAME_OVER_SMALL_FONT_SIZE: f32 = 96.;
@@ -291,15 +973,96 @@ pub const GAME_OVER_SMALL_FONT_SIZE: f32 = 96.;
 pub const SCORE_FONT_SIZE: f32 = 48.;
 pub const SCORE_GAP: f32 = 16.;
 
+pub const HISTOGRAM_FONT_SIZE: f32 = 32.;
+// the number of characters in the bar of an outcome that got every shot
+pub const HISTOGRAM_BAR_LENGTH: i32 = 20;
+
 impl Objective {
-    pub fn get_name(&self) -> &str {
+    pub fn get_name(&self) -> String {
         match self {
-            Objective::Measure0 => "Measure 0",
-            Objective::Measure1 => "Measure 1",
-            Objective::MeasurePhi => "Measure Phi",
-            Objective::MeasurePsi => "Measure Psi",
+            Objective::Measure0 => "Measure 0".into(),
+            Objective::Measure1 => "Measure 1".into(),
+            Objective::MeasurePhi => "Measure Phi".into(),
+            Objective::MeasurePsi => "Measure Psi".into(),
+            Objective::MeasureHTH => "Measure HTH|0>".into(),
+            Objective::MeasureHSTH => "Measure HSTH|0>".into(),
+            Objective::MeasurePlus => "Measure +".into(),
+            Objective::MeasureMinus => "Measure -".into(),
+            Objective::MeasurePlusI => "Measure +i".into(),
+            Objective::MeasureMinusI => "Measure -i".into(),
+            Objective::PreparePhiPlus => "Prepare |Phi+>".into(),
+            Objective::PreparePhiMinus => "Prepare |Phi->".into(),
+            Objective::PreparePsiPlus => "Prepare |Psi+>".into(),
+            Objective::PreparePsiMinus => "Prepare |Psi->".into(),
+            Objective::MeasureGHZ3 => "Measure GHZ".into(),
+            Objective::MeasureGHZ4 => "Measure 4 Wire GHZ".into(),
+            Objective::MeasureW4 => "Measure 4 Wire W".into(),
+            Objective::MeasureUniform3 => "Measure Uniform".into(),
+            Objective::Measure101 => "Measure |101>".into(),
+            Objective::Measure0110 => "Measure |0110>".into(),
+            Objective::MeasureOneQuarter => "Measure P(1)=0.25".into(),
+            Objective::MeasureUnevenPhi => "Measure P(00)=0.25 P(11)=0.75".into(),
+            Objective::MeasureControlledRy => "Measure P(00)=0.5 P(10)=0.25 P(11)=0.25".into(),
+            Objective::Teleport(_) => "Teleport to wire 2".into(),
+            // both oracle objectives share a name so the label does not give the answer away
+            Objective::OracleConstant | Objective::OracleBalanced => "Measure the oracle".into(),
+            Objective::FindMarked(_) => "Find the marked item".into(),
+            // every outcome with a nonzero probability, written from the bottom wire up
+            Objective::Generated { measure_count, .. } => {
+                let outcomes: Vec<String> = self
+                    .get_desired_state()
+                    .unwrap_or_else(|| DVector::zeros(0))
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &probability)| probability > TOLERANCE)
+                    .map(|(index, probability)| {
+                        let outcome: String = (0..*measure_count)
+                            .map(|i| if index & (1 << i) != 0 { '1' } else { '0' })
+                            .collect();
+                        format!("P({})={}", outcome, (probability * 1000.).round() / 1000.)
+                    })
+                    .collect();
+                format!("Measure {}", outcomes.join(" "))
+            }
+            // the wires are numbered by the measurement blocks, not by where they are placed
+            Objective::Expectation {
+                basis,
+                wire_count,
+                value,
+            } => {
+                let paulis: Vec<String> = (0..*wire_count)
+                    .map(|i| format!("{:?}{}", basis, i))
+                    .collect();
+                format!("Make <{}> = {}", paulis.join(" "), value)
+            }
+            Objective::BuildCZ
+            | Objective::BuildSwap
+            | Objective::BuildReversedCNOT
+            | Objective::BuildS => {
+                let name = match self {
+                    Objective::BuildCZ => "CZ",
+                    Objective::BuildSwap => "SWAP",
+                    Objective::BuildReversedCNOT => "Reversed CNOT",
+                    _ => "S",
+                };
+                let rows: Vec<String> = self.target_operator().map_or(vec![], |operator| {
+                    operator
+                        .row_iter()
+                        .map(|row| {
+                            row.iter()
+                                .map(|&entry| format_entry(entry))
+                                .collect::<Vec<String>>()
+                                .join(" ")
+                        })
+                        .collect()
+                });
+                format!("Build {} [{}]", name, rows.join("; "))
+            }
         }
     }
 }
 
 pub const TOLERANCE: f64 = 1e-6;
+// for objectives whose label rounds the probabilities
+pub const PROBABILITY_TOLERANCE: f64 = 0.01;
+pub const FIDELITY_THRESHOLD: f64 = 0.99;
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::*;
+
+// a random circuit with a gate on every column, drawn from the endless gate pool on one more wire than is measured, so the measured wires can end up entangled with the rest
+// returns the board and the locations of the measured wires in its last column
+pub fn generated_circuit(seed: u64, depth: i32, measure_count: i32) -> (Board, Vec<(i32, i32)>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let height = measure_count + 1;
+    let mut board = Board::new(depth + 1, height);
+    // mid-circuit measurements are left out since their outcome is random
+    let gates: Vec<(Gate, f32)> = GATES_WITHOUT_CONTROL
+        .iter()
+        .copied()
+        .filter(|(gate, _)| gate.operator().is_some())
+        .collect();
+    for x in 0..depth {
+        let target = rng.gen_range(0..height);
+        let gate = if rng.gen::<f32>() < ROTATION_GATE_CHANCE {
+            let angle = ROTATION_ANGLES[rng.gen_range(0..ROTATION_ANGLES.len())];
+            [Gate::Rx(angle), Gate::Ry(angle), Gate::Rz(angle)][rng.gen_range(0..3)]
+        } else {
+            gates
+                .choose_weighted(&mut rng, |&(_, weight)| weight)
+                .map_or(Gate::H, |&(gate, _)| gate)
+        };
+        board.set(
+            x,
+            target,
+            Some(Cell {
+                gate,
+                on_top: false,
+            }),
+        );
+        if rng.gen::<f32>() < GENERATED_CONTROL_CHANCE {
+            let control = (0..height).filter(|&y| y != target).choose(&mut rng);
+            if let (Some(control), Some(&control_gate)) = (control, CONTROL_GATES.choose(&mut rng))
+            {
+                board.set(
+                    x,
+                    control,
+                    Some(Cell {
+                        gate: control_gate,
+                        on_top: target < control,
+                    }),
+                );
+            }
+        }
+    }
+    let mut measure_wires: Vec<i32> = (0..height).choose_multiple(&mut rng, measure_count as usize);
+    measure_wires.sort();
+    let measure_block_locations = measure_wires.iter().map(|&y| (depth, y)).collect();
+    (board, measure_block_locations)
+}
+
+pub fn generate_objective(difficulty: &Difficulty) -> Objective {
+    Objective::Generated {
+        seed: rand::thread_rng().gen(),
+        depth: difficulty.depth,
+        measure_count: difficulty.measure_count,
+    }
+}
+
+pub fn cycle_difficulty(keys: Res<Input<KeyCode>>, mut difficulty: ResMut<Difficulty>) {
+    if keys.just_pressed(CYCLE_DIFFICULTY_KEYCODE) {
+        let index = DIFFICULTIES
+            .iter()
+            .position(|&other| other == *difficulty)
+            .map_or(0, |index| (index + 1) % DIFFICULTIES.len());
+        *difficulty = DIFFICULTIES[index];
+    }
+}
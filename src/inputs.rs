@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::*;
+
+// the input drawn at the left end of wire y, which shows the 0.png image for |0> and a text label otherwise
+#[derive(Component)]
+pub struct InitialStateLabel {
+    pub y: i32,
+}
+
+impl Inputs {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Inputs::Zero => "|0>",
+            Inputs::RandomPerGame => "Random per Game",
+            Inputs::RandomPerObjective => "Random per Objective",
+        }
+    }
+    // picks the initial state of every wire
+    pub fn pick(&self, challenge: Challenge) -> Vec<InitialState> {
+        if *self == Inputs::Zero || challenge != Challenge::Endless {
+            return vec![InitialState::Zero; Y_COUNT as usize];
+        }
+        let mut rng = rand::thread_rng();
+        (0..Y_COUNT)
+            .map(|_| {
+                *INITIAL_STATES
+                    .choose(&mut rng)
+                    .unwrap_or(&InitialState::Zero)
+            })
+            .collect()
+    }
+}
+
+pub fn cycle_inputs(keys: Res<Input<KeyCode>>, mut inputs: ResMut<Inputs>) {
+    if keys.just_pressed(CYCLE_INPUTS_KEYCODE) {
+        *inputs = match INPUTS.iter().position(|&other| other == *inputs) {
+            Some(index) => INPUTS.get(index + 1).copied().unwrap_or(INPUTS[0]),
+            None => INPUTS[0],
+        };
+    }
+}
+
+// check_game_restart picks new inputs for every game, this picks them when the mode changes or for every new objective
+pub fn update_initial_states(
+    inputs: Res<Inputs>,
+    objective: Res<Objective>,
+    challenge: Res<Challenge>,
+    mut board: ResMut<BoardCache>,
+) {
+    if inputs.is_changed() || (*inputs == Inputs::RandomPerObjective && objective.is_changed()) {
+        board.0.set_initial_states(inputs.pick(*challenge));
+    }
+}
+
+pub fn edit_initial_state_labels(
+    mut label_query: Query<(&InitialStateLabel, &mut Sprite, &Children)>,
+    mut text_query: Query<&mut Text>,
+    board: Res<BoardCache>,
+) {
+    for (label, mut sprite, children) in &mut label_query {
+        let initial_state = board.0.initial_states()[label.y as usize];
+        sprite.color = if initial_state == InitialState::Zero {
+            Color::WHITE
+        } else {
+            Color::NONE
+        };
+        for &child in children {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = if initial_state == InitialState::Zero {
+                    String::new()
+                } else {
+                    initial_state.get_name().into()
+                };
+            }
+        }
+    }
+}
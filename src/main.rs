@@ -1,13 +1,23 @@
 use bevy::asset::AssetMetaCheck;
 use bevy::{prelude::*, sprite::Anchor};
 
+use challenge::*;
 use constants::*;
+use generator::*;
+use inputs::*;
 use piece::*;
+use quant::*;
+use sampling::*;
 use stats::*;
 
+mod challenge;
 mod constants;
+mod generator;
+mod inputs;
 mod piece;
 mod quant;
+mod sampling;
+mod stabilizer;
 mod stats;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -23,6 +33,78 @@ pub enum Objective {
     Measure1,
     MeasurePhi,
     MeasurePsi,
+    MeasureHTH,
+    MeasureHSTH,
+    MeasurePlus,
+    MeasureMinus,
+    MeasurePlusI,
+    MeasureMinusI,
+    PreparePhiPlus,
+    PreparePhiMinus,
+    PreparePsiPlus,
+    PreparePsiMinus,
+    MeasureGHZ3,
+    MeasureGHZ4,
+    MeasureW4,
+    MeasureUniform3,
+    Measure101,
+    Measure0110,
+    MeasureOneQuarter,
+    MeasureUnevenPhi,
+    MeasureControlledRy,
+    Teleport(Angle),
+    OracleConstant,
+    OracleBalanced,
+    // bit i of the index is the outcome on wire i
+    FindMarked(i32),
+    // the distribution measured at the end of the circuit generator::generated_circuit makes from the seed, so it is always reachable
+    Generated {
+        seed: u64,
+        depth: i32,
+        measure_count: i32,
+    },
+    // the expectation value of the Pauli string with basis on every measured wire
+    Expectation {
+        basis: Basis,
+        wire_count: i32,
+        value: i32,
+    },
+    BuildCZ,
+    BuildSwap,
+    BuildReversedCNOT,
+    BuildS,
+}
+
+// how long the circuits behind generated objectives are, and how many of their wires are measured
+#[derive(Resource, PartialEq, Eq, Clone, Copy)]
+pub struct Difficulty {
+    pub depth: i32,
+    pub measure_count: i32,
+}
+
+#[derive(Resource, PartialEq, Eq, Clone, Copy)]
+pub enum Challenge {
+    Endless,
+    Teleportation,
+    DeutschJozsa,
+    Grover,
+    PhaseKickback,
+    Synthesis,
+}
+
+// strict scoring fails any wrong measurement, graded scoring awards points by how close it is and only fails poor matches
+#[derive(Resource, PartialEq, Eq, Clone, Copy)]
+pub enum Scoring {
+    Strict,
+    Graded,
+}
+
+// where the initial state of every wire comes from, challenges always start from |0>
+#[derive(Resource, PartialEq, Eq, Clone, Copy)]
+pub enum Inputs {
+    Zero,
+    RandomPerGame,
+    RandomPerObjective,
 }
 
 #[derive(Component)]
@@ -48,9 +130,25 @@ fn main() {
             shape: Shape::I,
             rotation: 0,
             pieces_since_objective: 0,
+            selected_measurement: 0,
         })
+        .insert_resource(BoardCache(Board::new(X_COUNT, Y_COUNT)))
         .insert_resource(Score { score: 0 })
         .insert_resource(Objective::Measure0)
+        .insert_resource(Challenge::Endless)
+        .insert_resource(ChallengeRound { started: false })
+        .insert_resource(DIFFICULTIES[0])
+        .insert_resource(Scoring::Strict)
+        .insert_resource(Inputs::Zero)
+        .insert_resource(Lives {
+            lives: STARTING_LIVES[0].0,
+            garbage_columns: 0,
+        })
+        .insert_resource(STARTING_LIVES[0])
+        .insert_resource(Shots {
+            count: None,
+            histogram: vec![],
+        })
         .insert_resource(AssetMetaCheck::Never)
         .add_state::<GameState>()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
@@ -66,19 +164,40 @@ fn main() {
             (
                 check_over,
                 generate_new_piece.after(check_over),
-                check_measurment,
+                update_board,
+                check_measurment.after(update_board),
+                // move_empty_lines would pull the blocks back before the garbage is spawned
+                inject_garbage.after(move_empty_lines),
                 falling_piece,
                 move_piece,
+                select_measurement,
                 rotate_piece,
                 clear_columns,
                 drop_piece,
                 move_empty_lines,
                 edit_objective_label,
                 edit_scoreboard,
+                start_challenge_round.after(check_game_restart),
             )
                 .run_if(in_state(GameState::Playing)),
         )
-        .add_systems(Update, check_game_restart)
+        .add_systems(
+            Update,
+            (
+                check_game_restart.after(cycle_challenge),
+                cycle_noise,
+                cycle_challenge,
+                cycle_difficulty,
+                cycle_shots,
+                cycle_scoring,
+                cycle_starting_lives,
+                cycle_inputs,
+                update_initial_states,
+                edit_initial_state_labels,
+                // also shows the shots of the measurement that lost the game
+                edit_histogram_label,
+            ),
+        )
         .add_systems(OnEnter(GameState::Lost), show_lose_screen)
         .add_systems(
             PostUpdate,
@@ -86,6 +205,8 @@ fn main() {
                 update_block_transforms,
                 hide_outside_blocks,
                 move_control_wires,
+                move_swap_wires,
+                highlight_selected_measurement,
             ),
         )
         .run();
@@ -115,15 +236,33 @@ pub fn setup_background(mut commands: Commands, asset_server: Res<AssetServer>)
             ),
             ..default()
         });
-        commands.spawn(SpriteBundle {
-            texture: asset_server.load("0.png"),
-            transform: Transform::from_xyz(
-                -REFERENCE_SCREEN_WIDTH / 2. + INITIAL_STATE_DISTANCE_FROM_RIGHT,
-                y as f32 * Y_GAPS - REFERENCE_SCREEN_HEIGHT / 2.,
-                1.,
-            ),
-            ..default()
-        });
+        commands
+            .spawn((
+                SpriteBundle {
+                    texture: asset_server.load("0.png"),
+                    transform: Transform::from_xyz(
+                        -REFERENCE_SCREEN_WIDTH / 2. + INITIAL_STATE_DISTANCE_FROM_RIGHT,
+                        y as f32 * Y_GAPS - REFERENCE_SCREEN_HEIGHT / 2.,
+                        1.,
+                    ),
+                    ..default()
+                },
+                InitialStateLabel { y: y - 1 },
+            ))
+            .with_children(|parent| {
+                parent.spawn(Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font_size: INITIAL_STATE_FONT_SIZE,
+                            color: Color::BLACK,
+                            ..default()
+                        },
+                    ),
+                    transform: Transform::from_xyz(0., 0., 1.),
+                    ..default()
+                });
+            });
     }
     commands.spawn((
         Text2dBundle {
@@ -161,6 +300,26 @@ pub fn setup_background(mut commands: Commands, asset_server: Res<AssetServer>)
         },
         Scoreboard,
     ));
+    commands.spawn((
+        Text2dBundle {
+            text: Text::from_section(
+                "",
+                TextStyle {
+                    font_size: HISTOGRAM_FONT_SIZE,
+                    color: Color::BLACK,
+                    ..default()
+                },
+            ),
+            transform: Transform::from_xyz(
+                REFERENCE_SCREEN_WIDTH / 2. - SCORE_GAP,
+                REFERENCE_SCREEN_HEIGHT / 2. - SCORE_GAP,
+                1.,
+            ),
+            text_anchor: Anchor::TopRight,
+            ..default()
+        },
+        HistogramLabel,
+    ));
     commands.spawn(AudioBundle {
         source: asset_server.load("music.ogg"),
         settings: PlaybackSettings::LOOP,
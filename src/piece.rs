@@ -3,7 +3,6 @@ use rand::prelude::*;
 use rand::seq::SliceRandom;
 
 use crate::constants::*;
-use crate::quant::*;
 use crate::*;
 
 #[derive(Component)]
@@ -26,61 +25,212 @@ pub struct Control {
 #[derive(Component)]
 pub struct ControlWire;
 
+// one half of a swap gate, partner is the piece number of the other half
+#[derive(Component)]
+pub struct Swap {
+    pub partner: i32,
+    pub on_top: bool,
+}
+
+#[derive(Component)]
+pub struct SwapWire;
+
+// a block of a measurement piece, index is the bit of the objective's outcome it measures
+#[derive(Component)]
+pub struct Measurement {
+    pub index: i32,
+}
+
+#[derive(Resource)]
+pub struct BoardCache(pub Board);
+
 #[derive(Resource)]
 pub struct PieceInfo {
     pub last_drop: f32,
     pub shape: Shape,
     pub rotation: i32,
     pub pieces_since_objective: i32,
+    // the block of a measurement piece moved on its own while SPREAD_MEASUREMENT_KEYCODE is held
+    pub selected_measurement: i32,
 }
 
+// overflowing the board costs a life and starts over on an empty board
 pub fn check_over(
-    block_query: Query<&Block, Without<Piece>>,
+    mut commands: Commands,
+    block_query: Query<(Entity, &Block), Without<Piece>>,
     mut next_state: ResMut<NextState<GameState>>,
+    mut lives: ResMut<Lives>,
+    mut round: ResMut<ChallengeRound>,
 ) {
-    if block_query.iter().any(|block| block.x >= X_COUNT) {
-        next_state.set(GameState::Lost);
+    if block_query.iter().any(|(_, block)| block.x >= X_COUNT)
+        && lose_life(&mut lives, &mut next_state)
+    {
+        for (entity, _) in &block_query {
+            commands.entity(entity).despawn_recursive();
+        }
+        lives.garbage_columns = 0;
+        // challenges place their fixed blocks again
+        round.started = false;
     }
 }
 
+pub fn board_from_blocks<'a>(
+    blocks: impl Iterator<Item = (&'a Block, Option<&'a Control>, Option<&'a Swap>)>,
+) -> Board {
+    let mut board = Board::new(X_COUNT, Y_COUNT);
+    for (block, control, swap) in blocks {
+        board.set(
+            block.x,
+            block.y,
+            Some(Cell {
+                gate: block.gate,
+                on_top: control.is_some_and(|control| control.on_top)
+                    || swap.is_some_and(|swap| swap.on_top),
+            }),
+        );
+    }
+    board
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_board(
+    block_query: Query<(&Block, Option<&Control>, Option<&Swap>), Without<Piece>>,
+    mut board: ResMut<BoardCache>,
+) {
+    board
+        .0
+        .copy_cells_from(&board_from_blocks(block_query.iter()));
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn check_measurment(
     mut commands: Commands,
     block_entity_query: Query<(Entity, &Block), Without<Piece>>,
-    block_query: Query<&Block, Without<Piece>>,
-    control_block_query: Query<(&Block, &Control), Without<Piece>>,
+    mut board: ResMut<BoardCache>,
     mut next_state: ResMut<NextState<GameState>>,
     mut score: ResMut<Score>,
     mut objective: ResMut<Objective>,
+    challenge: Res<Challenge>,
+    difficulty: Res<Difficulty>,
+    measurement_query: Query<(&Block, &Measurement), Without<Piece>>,
+    mut round: ResMut<ChallengeRound>,
+    mut shots: ResMut<Shots>,
+    scoring: Res<Scoring>,
+    mut lives: ResMut<Lives>,
 ) {
-    let mut measure_block_locations = vec![];
-    for (_, measure_block) in &block_entity_query {
-        if measure_block.gate == Gate::M {
-            measure_block_locations.push((measure_block.x, measure_block.y));
-        }
-    }
-    if measure_block_locations.len() > 0 {
-        if satisfies_objective(
-            objective.get_desired_state(),
-            &block_query,
-            &control_block_query,
-            measure_block_locations.clone(),
-        ) {
+    let mut measurements: Vec<(&Block, &Measurement)> = measurement_query.iter().collect();
+    // bit i of the measured outcome comes from the block with index i, wherever the player put it
+    measurements.sort_by_key(|(_, measurement)| measurement.index);
+    let measure_block_locations: Vec<(i32, i32)> = measurements
+        .iter()
+        .map(|(block, _)| (block.x, block.y))
+        .collect();
+    if !measure_block_locations.is_empty() {
+        let (passed, points) = match *scoring {
+            Scoring::Strict => (
+                challenge.is_satisfied_by(
+                    *objective,
+                    &mut board.0,
+                    &measure_block_locations,
+                    &mut shots,
+                ),
+                MEASUREMENT_POINTS,
+            ),
+            Scoring::Graded => {
+                let grade = challenge.grade(
+                    *objective,
+                    &mut board.0,
+                    &measure_block_locations,
+                    &mut shots,
+                );
+                (
+                    grade >= GRADED_PASS_THRESHOLD,
+                    (MEASUREMENT_POINTS as f64 * grade).round() as i32,
+                )
+            }
+        };
+        if passed {
             for (entity, block) in &block_entity_query {
                 if block.x < measure_block_locations[0].0 {
-                    score.score += 10;
+                    score.score += points;
                     commands.entity(entity).despawn_recursive();
                 }
             }
             for (measure_entity, measure_block) in &block_entity_query {
-                if measure_block.gate == Gate::M {
+                if let Gate::M(_) = measure_block.gate {
                     commands.entity(measure_entity).despawn_recursive();
                 }
             }
-            if let Some(&new_objective) = OBJECTIVES.choose(&mut rand::thread_rng()) {
+            if *challenge != Challenge::Endless {
+                // the next round starts on an empty board
+                for (entity, _) in &block_entity_query {
+                    commands.entity(entity).despawn_recursive();
+                }
+                round.started = false;
+            } else if rand::thread_rng().gen::<f32>() < GENERATED_OBJECTIVE_CHANCE {
+                *objective = generate_objective(&difficulty);
+            } else if let Some(&new_objective) = OBJECTIVES.choose(&mut rand::thread_rng()) {
                 *objective = new_objective;
             };
-        } else {
-            next_state.set(GameState::Lost);
+        } else if lose_life(&mut lives, &mut next_state) {
+            // the measurement is taken off so another can be tried
+            for (measure_entity, measure_block) in &block_entity_query {
+                if let Gate::M(_) = measure_block.gate {
+                    commands.entity(measure_entity).despawn_recursive();
+                }
+            }
+            // garbage in front of a challenge's fixed blocks could make its round unwinnable
+            if *challenge == Challenge::Endless {
+                lives.garbage_columns += 1;
+            }
+        }
+    }
+}
+
+// pushes every block right to make room for a column of fixed gates with one gap, which scrambles the wires until it is cleared
+// the column is dropped when the board is too full to push, since the failed measurement already cost its life
+pub fn inject_garbage(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut block_query: Query<(&mut Block, Has<Piece>)>,
+    mut lives: ResMut<Lives>,
+    challenge: Res<Challenge>,
+) {
+    if lives.garbage_columns <= 0 {
+        return;
+    }
+    // a full board keeps the garbage queued until clearing a column makes room for it
+    if block_query
+        .iter()
+        .any(|(block, is_piece)| !is_piece && block.x >= X_COUNT - 1)
+    {
+        return;
+    }
+    lives.garbage_columns -= 1;
+    for (mut block, _) in &mut block_query {
+        block.x += 1;
+    }
+    // only unitary gates, so the garbage scrambles the wires without measuring them
+    let gates: Vec<(Gate, f32)> = challenge
+        .gate_pool()
+        .gates
+        .iter()
+        .copied()
+        .filter(|(gate, _)| gate.operator().is_some())
+        .collect();
+    let mut rng = rand::thread_rng();
+    let gap = rng.gen_range(0..Y_COUNT);
+    for y in (0..Y_COUNT).filter(|&y| y != gap) {
+        if let Ok(&(gate, _)) = gates.choose_weighted(&mut rng, |&(_, weight)| weight) {
+            spawn_block(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                Block { x: 0, y, gate },
+                FIXED_BLOCK_COLOR,
+                false,
+            );
         }
     }
 }
@@ -134,9 +284,10 @@ pub fn falling_piece(
 }
 
 pub fn move_piece(
-    mut piece_query: Query<&mut Block, With<Piece>>,
+    mut piece_query: Query<(&mut Block, Option<&Measurement>), With<Piece>>,
     block_query: Query<&Block, Without<Piece>>,
     keys: Res<Input<KeyCode>>,
+    piece_info: Res<PieceInfo>,
 ) {
     if !keys.just_pressed(PIECE_DOWN_KEYCODE) && !keys.just_pressed(PIECE_UP_KEYCODE) {
         return;
@@ -149,25 +300,79 @@ pub fn move_piece(
     } else {
         1
     };
-    if piece_query.iter().all(|piece_location| {
+    // the selected block of a measurement piece moves alone, swapping places with the piece's block in its way
+    if keys.pressed(SPREAD_MEASUREMENT_KEYCODE) && matches!(piece_info.shape, Shape::M) {
+        let Some((x, y)) = piece_query
+            .iter()
+            .find(|(_, measurement)| {
+                measurement
+                    .is_some_and(|measurement| measurement.index == piece_info.selected_measurement)
+            })
+            .map(|(block, _)| (block.x, block.y))
+        else {
+            return;
+        };
+        let target_y = y + ymove;
+        if !(0..Y_COUNT).contains(&target_y)
+            || block_query
+                .iter()
+                .any(|block| block.x == x && block.y == target_y)
+        {
+            return;
+        }
+        for (mut block, _) in &mut piece_query {
+            if block.y == y {
+                block.y = target_y;
+            } else if block.y == target_y {
+                block.y = y;
+            }
+        }
+        return;
+    }
+    if piece_query.iter().all(|(piece_location, _)| {
         !block_query.iter().any(|block_location| {
             block_location.x == piece_location.x && block_location.y == (piece_location.y + ymove)
         }) && piece_location.x >= 0
             && (piece_location.y + ymove) >= 0
             && (piece_location.y + ymove) < Y_COUNT
     }) {
-        for mut piece_location in &mut piece_query {
+        for (mut piece_location, _) in &mut piece_query {
             piece_location.y += ymove;
         }
     }
 }
 
+pub fn select_measurement(
+    measurement_query: Query<With<Measurement>, With<Piece>>,
+    keys: Res<Input<KeyCode>>,
+    mut piece_info: ResMut<PieceInfo>,
+) {
+    if keys.just_pressed(SELECT_MEASUREMENT_KEYCODE) && !measurement_query.is_empty() {
+        piece_info.selected_measurement =
+            (piece_info.selected_measurement + 1) % measurement_query.iter().count() as i32;
+    }
+}
+
+pub fn highlight_selected_measurement(
+    mut measurement_query: Query<(&mut Sprite, &Measurement, Has<Piece>)>,
+    piece_info: Res<PieceInfo>,
+) {
+    for (mut sprite, measurement, is_piece) in &mut measurement_query {
+        sprite.color = if is_piece && measurement.index == piece_info.selected_measurement {
+            SELECTED_MEASUREMENT_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
 pub fn rotate_piece(
     mut piece_query: Query<(&mut Block, &Piece)>,
     block_query: Query<&Block, Without<Piece>>,
     keys: Res<Input<KeyCode>>,
     mut piece_info: ResMut<PieceInfo>,
-    mut control_piece_query: Query<&mut Control, With<Piece>>,
+    mut control_piece_query: Query<(&mut Control, &Piece)>,
+    mut swap_piece_query: Query<(&mut Swap, &Piece)>,
 ) {
     if !keys.just_pressed(ROTATE_PIECE_CLOCKWISE)
         && !keys.just_pressed(ROTATE_PIECE_COUNTERCLOCKWISE)
@@ -213,8 +418,25 @@ pub fn rotate_piece(
             piece_location.x += wall_kicks_dx + rotation_dx;
             piece_location.y += wall_kicks_dy + rotation_dy;
         }
-        for mut control in &mut control_piece_query {
-            control.on_top = piece_info.shape.control_on_top(next_rotation);
+        let control_numbers: Vec<i32> = control_piece_query
+            .iter()
+            .map(|(_, piece)| piece.number)
+            .collect();
+        for (mut control, piece) in &mut control_piece_query {
+            control.on_top =
+                piece_info
+                    .shape
+                    .control_on_top(piece.number, next_rotation, &control_numbers);
+        }
+        for (mut swap, piece) in &mut swap_piece_query {
+            swap.on_top = piece_info
+                .shape
+                .rotation_location(swap.partner, next_rotation)
+                .1
+                < piece_info
+                    .shape
+                    .rotation_location(piece.number, next_rotation)
+                    .1;
         }
         piece_info.rotation = next_rotation;
     }
@@ -262,7 +484,7 @@ pub fn clear_columns(
         if (0..Y_COUNT).all(|y| {
             block_query
                 .iter()
-                .any(|(_, block)| block.x == x && block.y == y && block.gate != Gate::M)
+                .any(|(_, block)| block.x == x && block.y == y && !matches!(block.gate, Gate::M(_)))
         }) {
             columns_cleared += 1;
             for (entity, block_location) in &block_query {
@@ -310,18 +532,58 @@ pub fn update_block_transforms(mut query: Query<(&mut Transform, &Block)>) {
 }
 
 pub fn move_control_wires(
-    control_piece_query: Query<(&Children, &Control), With<Piece>>,
-    mut control_piece_wire_query: Query<&mut Transform, With<ControlWire>>,
+    control_query: Query<(&Children, &Block, &Control, Has<Piece>)>,
+    block_query: Query<(&Block, Has<Piece>)>,
+    mut control_wire_query: Query<(&mut Transform, &mut Sprite), With<ControlWire>>,
+) {
+    for (children, control_block, control, is_piece) in &control_query {
+        // falling pieces only link to their own blocks, stationary ones to the rest of the board
+        let distance = control_target(control_block.y, control.on_top, Y_COUNT, |y| {
+            block_query
+                .iter()
+                .find(|&(block, block_is_piece)| {
+                    block.x == control_block.x && block.y == y && block_is_piece == is_piece
+                })
+                .map(|(block, _)| block.gate)
+        })
+        .map_or(1, |target_y| (target_y - control_block.y).abs());
+        for &child in children.iter() {
+            if let Ok((mut transform, mut sprite)) = control_wire_query.get_mut(child) {
+                transform.translation.y =
+                    if control.on_top { -1. } else { 1. } * distance as f32 * Y_GAPS / 2.;
+                sprite.custom_size = Some(Vec2::new(WIRE_WIDTH, distance as f32 * Y_GAPS));
+            }
+        }
+    }
+}
+
+pub fn move_swap_wires(
+    swap_query: Query<(&Children, &Block, &Swap)>,
+    block_query: Query<(&Block, Option<&Swap>)>,
+    mut swap_wire_query: Query<(&mut Transform, &mut Visibility), With<SwapWire>>,
 ) {
-    for (children, control) in &control_piece_query {
+    for (children, swap_block, swap) in &swap_query {
+        let partner_y = swap_block.y + if swap.on_top { -1 } else { 1 };
+        let paired = block_query.iter().any(|(block, partner)| {
+            block.x == swap_block.x
+                && block.y == partner_y
+                && block.gate == swap_block.gate
+                && partner.is_some_and(|partner| partner.on_top != swap.on_top)
+        });
         for &child in children.iter() {
-            if let Ok(mut transform) = control_piece_wire_query.get_mut(child) {
-                transform.translation.y = if control.on_top { -1. } else { 1. } * Y_GAPS / 2.;
+            if let Ok((mut transform, mut visibility)) = swap_wire_query.get_mut(child) {
+                transform.translation.y = if swap.on_top { -1. } else { 1. } * Y_GAPS / 2.;
+                *visibility = if paired {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn generate_new_piece(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -330,6 +592,7 @@ pub fn generate_new_piece(
     mut piece_info: ResMut<PieceInfo>,
     measurment_image: Res<MeasureImage>,
     objective: Res<Objective>,
+    challenge: Res<Challenge>,
 ) {
     if !piece_query.is_empty() {
         return;
@@ -337,108 +600,291 @@ pub fn generate_new_piece(
     if piece_info.pieces_since_objective >= OBJECTIVE_PERIOD {
         piece_info.shape = Shape::M;
         piece_info.pieces_since_objective = 0;
+        piece_info.selected_measurement = 0;
         for y in 0..objective.measure_count() {
-            commands.spawn((
-                Block {
-                    x: X_COUNT - 1,
-                    y,
-                    gate: Gate::M,
-                },
-                Piece { number: 0 },
-                SpriteBundle {
-                    texture: measurment_image.0.clone(),
-                    transform: Transform::from_xyz(0., 0., 1.),
-                    ..default()
-                },
-            ));
-        }
-    } else if let Some(shape) = SHAPES.choose(&mut rand::thread_rng()) {
-        piece_info.shape = *shape;
-        piece_info.rotation = 0;
-        piece_info.pieces_since_objective += 1;
-        for number in 0..4 {
-            let (x, y) = shape.rotation_location(number, 0);
-            if let Some(&gate) = if shape.can_control_spawn(number)
-                && rand::thread_rng().gen::<f32>() < CONTROL_GATE_CHANCE
-            {
-                CONTROL_GATES.choose(&mut rand::thread_rng())
-            } else {
-                GATES_WITHOUT_CONTROL.choose(&mut rand::thread_rng())
-            } {
-                let mut x = commands.spawn((
+            commands
+                .spawn((
                     Block {
-                        x: X_COUNT - 1 + x,
+                        x: X_COUNT - 1,
                         y,
-                        gate,
+                        gate: Gate::M(objective.basis()),
                     },
-                    Piece { number },
-                ));
-                if CONTROL_GATES.contains(&gate) {
-                    x.insert((
-                        Control {
-                            on_top: shape.control_on_top(0),
-                        },
-                        MaterialMesh2dBundle {
-                            mesh: meshes
-                                .add(shape::Circle::new(CONTROL_OUTER_RADIUS).into())
-                                .into(),
-                            material: materials.add(ColorMaterial::from(shape.color())),
-                            transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
-                            ..default()
-                        },
-                    ));
-                    x.with_children(|parent| {
-                        parent.spawn((
-                            SpriteBundle {
-                                sprite: Sprite {
-                                    color: shape.color(),
-                                    custom_size: Some(Vec2 {
-                                        x: WIRE_WIDTH,
-                                        y: Y_GAPS,
-                                    }),
+                    Piece { number: 0 },
+                    Measurement { index: y },
+                    SpriteBundle {
+                        texture: measurment_image.0.clone(),
+                        transform: Transform::from_xyz(0., 0., 1.),
+                        ..default()
+                    },
+                ))
+                .with_children(|parent| {
+                    if objective.measure_count() > 1 {
+                        parent.spawn(Text2dBundle {
+                            text: Text::from_section(
+                                y.to_string(),
+                                TextStyle {
+                                    font_size: BASIS_FONT_SIZE,
+                                    color: Color::BLACK,
                                     ..default()
                                 },
-                                ..default()
-                            },
-                            ControlWire,
-                        ));
-                        if gate == Gate::AC {
-                            parent.spawn(MaterialMesh2dBundle {
-                                mesh: meshes
-                                    .add(shape::Circle::new(CONTROL_INNER_RADIUS).into())
-                                    .into(),
-                                material: materials.add(ColorMaterial::from(Color::WHITE)),
-                                transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
-                                ..default()
-                            });
-                        }
-                    });
-                } else {
-                    x.insert(SpriteBundle {
-                        sprite: Sprite {
-                            color: shape.color(),
-                            custom_size: Some(Vec2::new(OPERATOR_SIZE, OPERATOR_SIZE)),
+                            ),
+                            transform: Transform::from_xyz(
+                                -OPERATOR_SIZE / 2.,
+                                OPERATOR_SIZE / 2.,
+                                1.,
+                            ),
+                            text_anchor: Anchor::TopLeft,
                             ..default()
-                        },
-                        transform: Transform::from_xyz(0., 0., 1.),
-                        ..default()
-                    });
-                    x.with_children(|parent| {
+                        });
+                    }
+                    if objective.basis() != Basis::Z {
                         parent.spawn(Text2dBundle {
                             text: Text::from_section(
-                                gate.to_string(),
+                                format!("{:?}", objective.basis()),
                                 TextStyle {
-                                    font_size: OPERATOR_FONT_SIZE,
+                                    font_size: BASIS_FONT_SIZE,
                                     color: Color::BLACK,
                                     ..default()
                                 },
                             ),
-                            transform: Transform::from_xyz(0., 0., 1.),
+                            transform: Transform::from_xyz(
+                                OPERATOR_SIZE / 2.,
+                                -OPERATOR_SIZE / 2.,
+                                1.,
+                            ),
+                            text_anchor: Anchor::BottomRight,
                             ..default()
                         });
-                    });
+                    }
+                });
+        }
+    } else if let Some(shape) = SHAPES.choose(&mut rand::thread_rng()) {
+        piece_info.shape = *shape;
+        piece_info.rotation = 0;
+        piece_info.pieces_since_objective += 1;
+        let gate_pool = challenge.gate_pool();
+        let swap_numbers: Vec<i32> = (0..4)
+            .find(|&number| shape.can_control_spawn(number))
+            .filter(|_| {
+                gate_pool.special_gates && rand::thread_rng().gen::<f32>() < SWAP_GATE_CHANCE
+            })
+            .and_then(|number| Some(vec![number, shape.vertical_neighbour(number, 0)?]))
+            .unwrap_or_default();
+        let swap_gate = SWAP_GATES.choose(&mut rand::thread_rng());
+        let gates: Vec<Gate> = (0..4)
+            .filter_map(|number| {
+                if swap_numbers.contains(&number) {
+                    swap_gate.copied()
+                } else if (shape.can_control_spawn(number)
+                    && rand::thread_rng().gen::<f32>() < CONTROL_GATE_CHANCE)
+                    || (shape.can_extra_control_spawn(number)
+                        && rand::thread_rng().gen::<f32>() < EXTRA_CONTROL_GATE_CHANCE)
+                {
+                    gate_pool.controls.choose(&mut rand::thread_rng()).copied()
+                } else if gate_pool.special_gates
+                    && rand::thread_rng().gen::<f32>() < RESET_GATE_CHANCE
+                {
+                    RESET_GATES.choose(&mut rand::thread_rng()).copied()
+                } else if gate_pool.special_gates
+                    && rand::thread_rng().gen::<f32>() < ROTATION_GATE_CHANCE
+                {
+                    ROTATION_ANGLES
+                        .choose(&mut rand::thread_rng())
+                        .and_then(|&angle| {
+                            [Gate::Rx(angle), Gate::Ry(angle), Gate::Rz(angle)]
+                                .choose(&mut rand::thread_rng())
+                                .copied()
+                        })
+                } else {
+                    gate_pool
+                        .gates
+                        .choose_weighted(&mut rand::thread_rng(), |&(_, weight)| weight)
+                        .ok()
+                        .map(|&(gate, _)| gate)
                 }
+            })
+            .collect();
+        let control_numbers: Vec<i32> = (0..4)
+            .filter(|&number| {
+                CONTROL_GATES.contains(&gates[number as usize])
+                    || gates[number as usize] == Gate::CC
+            })
+            .collect();
+        for number in 0..4 {
+            let (x, y) = shape.rotation_location(number, 0);
+            let gate = gates[number as usize];
+            let partner = swap_numbers
+                .iter()
+                .copied()
+                .find(|&other| other != number)
+                .unwrap_or(number);
+            let on_top = if SWAP_GATES.contains(&gate) {
+                shape.rotation_location(partner, 0).1 < y
+            } else {
+                shape.control_on_top(number, 0, &control_numbers)
+            };
+            let entity = spawn_block(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                Block {
+                    x: X_COUNT - 1 + x,
+                    y,
+                    gate,
+                },
+                shape.color(),
+                on_top,
+            );
+            commands.entity(entity).insert(Piece { number });
+            if SWAP_GATES.contains(&gate) {
+                commands.entity(entity).insert(Swap { partner, on_top });
             }
         }
     }
 }
+
+// spawns a block drawn as its gate, controls also get a Control component pointing down if on_top is set
+pub fn spawn_block(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    block: Block,
+    color: Color,
+    on_top: bool,
+) -> Entity {
+    let gate = block.gate;
+    let mut x = commands.spawn(block);
+    if CONTROL_GATES.contains(&gate) || gate == Gate::CC {
+        x.insert((
+            Control { on_top },
+            MaterialMesh2dBundle {
+                mesh: meshes
+                    .add(shape::Circle::new(CONTROL_OUTER_RADIUS).into())
+                    .into(),
+                material: materials.add(ColorMaterial::from(color)),
+                transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
+                ..default()
+            },
+        ));
+        x.with_children(|parent| {
+            // classical controls are drawn with the double wire of a classical bit
+            let wire_offsets: &[f32] = if gate == Gate::CC {
+                &[-WIRE_WIDTH, WIRE_WIDTH]
+            } else {
+                &[0.]
+            };
+            for &wire_offset in wire_offsets {
+                parent.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color,
+                            custom_size: Some(Vec2 {
+                                x: WIRE_WIDTH,
+                                y: Y_GAPS,
+                            }),
+                            ..default()
+                        },
+                        transform: Transform::from_xyz(wire_offset, 0., 0.),
+                        ..default()
+                    },
+                    ControlWire,
+                ));
+            }
+            if gate == Gate::AC {
+                parent.spawn(MaterialMesh2dBundle {
+                    mesh: meshes
+                        .add(shape::Circle::new(CONTROL_INNER_RADIUS).into())
+                        .into(),
+                    material: materials.add(ColorMaterial::from(Color::WHITE)),
+                    transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
+                    ..default()
+                });
+            }
+        });
+    } else if SWAP_GATES.contains(&gate) {
+        x.insert(SpatialBundle::from_transform(Transform::from_xyz(
+            0., 0., 1.,
+        )));
+        x.with_children(|parent| {
+            for angle in [std::f32::consts::FRAC_PI_4, -std::f32::consts::FRAC_PI_4] {
+                parent.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2::new(SWAP_STROKE_WIDTH, SWAP_SIZE)),
+                        ..default()
+                    },
+                    transform: Transform::from_rotation(Quat::from_rotation_z(angle)),
+                    ..default()
+                });
+            }
+            parent.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color,
+                        custom_size: Some(Vec2 {
+                            x: WIRE_WIDTH,
+                            y: Y_GAPS,
+                        }),
+                        ..default()
+                    },
+                    ..default()
+                },
+                SwapWire,
+            ));
+            parent.spawn(Text2dBundle {
+                text: Text::from_section(
+                    gate.to_string(),
+                    TextStyle {
+                        font_size: SWAP_FONT_SIZE,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_xyz(SWAP_SIZE / 2., SWAP_SIZE / 2., 1.),
+                text_anchor: Anchor::BottomLeft,
+                ..default()
+            });
+        });
+    } else {
+        x.insert(SpriteBundle {
+            sprite: Sprite {
+                color,
+                custom_size: Some(Vec2::new(OPERATOR_SIZE, OPERATOR_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., 1.),
+            ..default()
+        });
+        x.with_children(|parent| {
+            parent.spawn(Text2dBundle {
+                text: Text::from_sections([
+                    TextSection::new(
+                        gate.to_string(),
+                        TextStyle {
+                            font_size: if gate.to_string().len() > 2 {
+                                LONG_OPERATOR_FONT_SIZE
+                            } else {
+                                OPERATOR_FONT_SIZE
+                            },
+                            color: Color::BLACK,
+                            ..default()
+                        },
+                    ),
+                    TextSection::new(
+                        gate.angle()
+                            .map_or(String::new(), |angle| format!("\n{}", angle)),
+                        TextStyle {
+                            font_size: ANGLE_FONT_SIZE,
+                            color: Color::BLACK,
+                            ..default()
+                        },
+                    ),
+                ])
+                .with_alignment(TextAlignment::Center),
+                transform: Transform::from_xyz(0., 0., 1.),
+                ..default()
+            });
+        });
+    }
+    x.id()
+}
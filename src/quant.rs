@@ -1,172 +1,913 @@
-use bevy::prelude::*;
 use nalgebra::*;
 
 use crate::constants::*;
-use crate::*;
+use crate::stabilizer::*;
 
-pub fn get_operator_of_column(
-    block_query: &Query<&Block, Without<Piece>>,
-    control_block_query: &Query<(&Block, &Control), Without<Piece>>,
-    x: i32,
-) -> DMatrix<Complex<f64>> {
-    let mut result: DMatrix<Complex<f64>> = dmatrix![Complex::new(1., 0.)];
-    for y in 0..Y_COUNT {
-        if let Some(block) = block_query
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub gate: Gate,
+    pub on_top: bool,
+}
+
+// the outcomes of the mid-circuit measurements so far, with the density matrix they leave scaled by their probability
+#[derive(Clone)]
+pub struct Branch {
+    // the wire bits whose last mid-circuit measurement measured 1
+    pub register: usize,
+    pub density: DMatrix<Complex<f64>>,
+}
+
+#[derive(Clone)]
+pub struct Board {
+    pub width: i32,
+    pub height: i32,
+    cells: Vec<Option<Cell>>,
+    // states[x] is the state after applying columns 0..=x, cached for every x < states.len()
+    states: Vec<DVector<Complex<f64>>>,
+    // noise applied to every wire after each column, which switches the simulation to density matrices
+    noise: Option<Noise>,
+    // branches[x] holds every possible outcome of the mid-circuit measurements after applying columns 0..=x, cached like states
+    branches: Vec<Vec<Branch>>,
+    // the state each wire starts in before column 0
+    initial_states: Vec<InitialState>,
+}
+
+impl Board {
+    pub fn new(width: i32, height: i32) -> Self {
+        Board {
+            width,
+            height,
+            cells: vec![None; (width * height) as usize],
+            states: vec![],
+            noise: None,
+            branches: vec![],
+            initial_states: vec![InitialState::Zero; height as usize],
+        }
+    }
+
+    pub fn initial_states(&self) -> &[InitialState] {
+        &self.initial_states
+    }
+
+    pub fn set_initial_states(&mut self, initial_states: Vec<InitialState>) {
+        if self.initial_states != initial_states {
+            self.initial_states = initial_states;
+            self.invalidate_from(0);
+        }
+    }
+
+    // the product of the wires' initial states
+    fn initial_state(&self) -> DVector<Complex<f64>> {
+        DVector::from_fn(2_usize.pow(self.height as u32), |idx, _| {
+            (0..self.height).fold(Complex::new(1., 0.), |amplitude, y| {
+                let (zero, one) = self.initial_states[y as usize].amplitudes();
+                amplitude
+                    * if idx & self.wire_bit(y) != 0 {
+                        one
+                    } else {
+                        zero
+                    }
+            })
+        })
+    }
+
+    pub fn noise(&self) -> Option<Noise> {
+        self.noise
+    }
+
+    pub fn set_noise(&mut self, noise: Option<Noise>) {
+        if self.noise != noise {
+            self.noise = noise;
+            self.branches.clear();
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Cell> {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return None;
+        }
+        self.cells[(x * self.height + y) as usize]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, cell: Option<Cell>) {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return;
+        }
+        let index = (x * self.height + y) as usize;
+        if self.cells[index] != cell {
+            self.cells[index] = cell;
+            self.invalidate_from(x);
+        }
+    }
+
+    // only invalidates the columns that actually differ from the other board
+    pub fn copy_cells_from(&mut self, other: &Board) {
+        for x in 0..self.width {
+            for y in 0..self.height {
+                self.set(x, y, other.get(x, y));
+            }
+        }
+    }
+
+    pub fn invalidate_from(&mut self, x: i32) {
+        self.states.truncate(x.max(0) as usize);
+        self.branches.truncate(x.max(0) as usize);
+    }
+
+    fn wire_bit(&self, y: i32) -> usize {
+        1 << (self.height - 1 - y)
+    }
+
+    // returns each wire controlling the operator at (x, y), paired with whether it is an anticontrol
+    pub fn control_wires(&self, x: i32, y: i32) -> Vec<(i32, bool)> {
+        (0..self.height)
+            .filter_map(|control_y| {
+                let control = self.get(x, control_y)?;
+                if CONTROL_GATES.contains(&control.gate)
+                    && control_target(control_y, control.on_top, self.height, |y| {
+                        self.get(x, y).map(|cell| cell.gate)
+                    }) == Some(y)
+                {
+                    Some((control_y, control.gate == Gate::AC))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // returns each wire classically controlling the operator at (x, y)
+    pub fn classical_control_wires(&self, x: i32, y: i32) -> Vec<i32> {
+        (0..self.height)
+            .filter(|&control_y| {
+                self.get(x, control_y).is_some_and(|control| {
+                    control.gate == Gate::CC
+                        && control_target(control_y, control.on_top, self.height, |y| {
+                            self.get(x, y).map(|cell| cell.gate)
+                        }) == Some(y)
+                })
+            })
+            .collect()
+    }
+
+    // the outcome of the last mid-circuit measurement on wire y in the register, or 0 if there is none
+    pub fn classical_bit(&self, y: i32, register: usize) -> bool {
+        register & self.wire_bit(y) != 0
+    }
+
+    // returns the (mask, value) the control wires of the operator at (x, y) must match
+    pub fn controls_of(&self, x: i32, y: i32) -> (usize, usize) {
+        let mut control_mask = 0;
+        let mut control_value = 0;
+        for (control_y, anti) in self.control_wires(x, y) {
+            control_mask |= self.wire_bit(control_y);
+            if !anti {
+                control_value |= self.wire_bit(control_y);
+            }
+        }
+        (control_mask, control_value)
+    }
+
+    // true if columns 0..=x have no mid-circuit measurements, classical controls, or reset gates, so they act as one operator
+    pub fn is_unitary(&self, x: i32) -> bool {
+        (0..x + 1).all(|x| {
+            (0..self.height).all(|y| {
+                !self.get(x, y).is_some_and(|cell| {
+                    cell.gate == Gate::Measure
+                        || cell.gate == Gate::CC
+                        || cell.gate.preparation().is_some()
+                })
+            })
+        })
+    }
+
+    // true if every gate in columns 0..=x can be simulated by the stabilizer backend
+    pub fn is_clifford(&self, x: i32) -> bool {
+        (0..x + 1).all(|x| {
+            (0..self.height).all(|y| match self.get(x, y) {
+                Some(Cell {
+                    gate: Gate::M(_), ..
+                }) => true,
+                Some(Cell {
+                    gate: Gate::Measure | Gate::CC | Gate::Reset | Gate::PreparePlus,
+                    ..
+                }) => false,
+                Some(cell) if SWAP_GATES.contains(&cell.gate) => {
+                    CLIFFORD_GATES.contains(&cell.gate)
+                }
+                Some(cell) if cell.gate.operator().is_some() => {
+                    match self.control_wires(x, y).len() {
+                        0 => CLIFFORD_GATES.contains(&cell.gate),
+                        1 => CONTROLLED_CLIFFORD_GATES.contains(&cell.gate),
+                        _ => false,
+                    }
+                }
+                _ => true,
+            })
+        })
+    }
+
+    // returns the lower wire and gate of every swap gate in column x whose two halves point at each other
+    pub fn swap_pairs(&self, x: i32) -> Vec<(i32, Gate)> {
+        (0..self.height)
+            .filter_map(|y| {
+                let lower = self.get(x, y)?;
+                let upper = self.get(x, y + 1)?;
+                if SWAP_GATES.contains(&lower.gate)
+                    && upper.gate == lower.gate
+                    && !lower.on_top
+                    && upper.on_top
+                {
+                    Some((y, lower.gate))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // works on a state vector, or on every column of a matrix, with classical controls reading the register
+    pub fn apply_column<C: Dim, S: StorageMut<Complex<f64>, Dyn, C>>(
+        &self,
+        x: i32,
+        state: &mut Matrix<Complex<f64>, Dyn, C, S>,
+        register: usize,
+    ) {
+        for y in 0..self.height {
+            if let Some(operator) = self.get(x, y).and_then(|cell| cell.gate.operator()) {
+                if !self
+                    .classical_control_wires(x, y)
+                    .iter()
+                    .all(|&control_y| self.classical_bit(control_y, register))
+                {
+                    continue;
+                }
+                let (control_mask, control_value) = self.controls_of(x, y);
+                apply_operator(
+                    state,
+                    self.wire_bit(y),
+                    &operator,
+                    control_mask,
+                    control_value,
+                );
+            }
+        }
+        for (y, gate) in self.swap_pairs(x) {
+            if let Some(operator) = gate.two_qubit_operator() {
+                apply_two_qubit_operator(state, self.wire_bit(y + 1), self.wire_bit(y), &operator);
+            }
+        }
+    }
+
+    // the kraus operators of the reset or preparation gate at (x, y), which measure the wire and turn either outcome into its state
+    fn preparation_channel(&self, x: i32, y: i32) -> Option<Vec<Matrix2<Complex<f64>>>> {
+        let initial_state = self.get(x, y)?.gate.preparation()?;
+        let preparation = initial_state
+            .preparation()
             .iter()
-            .find(|block_location| block_location.x == x && block_location.y == y)
-        {
-            if let Some(operator) = block.gate.operator() {
-                let mut kroneckered = false;
-                for (control_block, control) in control_block_query {
-                    if control_block.x == x && control_block.y == y + 1 && control.on_top {
-                        result = result.kronecker(&if control_block.gate == Gate::C {
-                            Matrix4::new(
-                                Complex::new(1., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                operator[(0, 0)],
-                                Complex::new(0., 0.),
-                                operator[(0, 1)],
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(1., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                operator[(1, 0)],
-                                Complex::new(0., 0.),
-                                operator[(1, 1)],
-                            )
-                        } else {
-                            Matrix4::new(
-                                operator[(0, 0)],
-                                Complex::new(0., 0.),
-                                operator[(0, 1)],
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(1., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                operator[(1, 0)],
-                                Complex::new(0., 0.),
-                                operator[(1, 1)],
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(1., 0.),
-                            )
-                        });
-                        kroneckered = true;
+            .filter_map(|gate| gate.operator())
+            .fold(Matrix2::identity(), |product, operator| operator * product);
+        let zero = Complex::new(0., 0.);
+        let one = Complex::new(1., 0.);
+        // |0><0|, and X |1><1| to flip the wire back to |0>
+        Some(vec![
+            preparation * Matrix2::new(one, zero, zero, zero),
+            preparation * Matrix2::new(zero, one, zero, zero),
+        ])
+    }
+
+    // only follows the operators, so it is only used while the columns are unitary
+    pub fn state_of_column(&mut self, x: i32) -> &DVector<Complex<f64>> {
+        while self.states.len() <= x as usize {
+            let mut state = if let Some(state) = self.states.last() {
+                state.clone()
+            } else {
+                self.initial_state()
+            };
+            self.apply_column(self.states.len() as i32, &mut state, 0);
+            self.states.push(state);
+        }
+        &self.states[x as usize]
+    }
+
+    // every mid-circuit measurement splits each branch in two, and branches whose registers end up equal are merged back
+    pub fn branches_of_column(&mut self, x: i32) -> &[Branch] {
+        while self.branches.len() <= x as usize {
+            let mut branches = if let Some(branches) = self.branches.last() {
+                branches.clone()
+            } else {
+                let state = self.initial_state();
+                vec![Branch {
+                    register: 0,
+                    density: &state * state.adjoint(),
+                }]
+            };
+            let column = self.branches.len() as i32;
+            for branch in &mut branches {
+                // U rho U^dagger, using (U rho)^dagger = rho U^dagger since rho is hermitian
+                self.apply_column(column, &mut branch.density, branch.register);
+                branch.density = branch.density.adjoint();
+                self.apply_column(column, &mut branch.density, branch.register);
+                for y in 0..self.height {
+                    if let Some(kraus_operators) = self.preparation_channel(column, y) {
+                        branch.density =
+                            apply_channel(&branch.density, self.wire_bit(y), &kraus_operators);
                     }
-                    if control_block.x == x && control_block.y == y - 1 && !control.on_top {
-                        result = result.kronecker(&if control_block.gate == Gate::C {
-                            Matrix4::new(
-                                Complex::new(1., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(1., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                operator[(0, 0)],
-                                operator[(0, 1)],
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                operator[(1, 0)],
-                                operator[(1, 1)],
-                            )
+                }
+            }
+            for y in 0..self.height {
+                if self.get(column, y).map(|cell| cell.gate) != Some(Gate::Measure) {
+                    continue;
+                }
+                let bit = self.wire_bit(y);
+                let mut measured: Vec<Branch> = vec![];
+                for branch in &branches {
+                    for outcome in [false, true] {
+                        let mut density = branch.density.clone();
+                        collapse_density(&mut density, bit, outcome);
+                        if density.trace().re <= TOLERANCE {
+                            continue;
+                        }
+                        let register = if outcome {
+                            branch.register | bit
+                        } else {
+                            branch.register & !bit
+                        };
+                        if let Some(same) = measured.iter_mut().find(|b| b.register == register) {
+                            same.density += density;
                         } else {
-                            Matrix4::new(
-                                operator[(0, 0)],
-                                operator[(0, 1)],
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                operator[(1, 0)],
-                                operator[(1, 1)],
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(1., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(0., 0.),
-                                Complex::new(1., 0.),
-                            )
-                        });
-                        kroneckered = true;
+                            measured.push(Branch { register, density });
+                        }
                     }
                 }
-                if !kroneckered {
-                    result = result.kronecker(&operator);
+                branches = measured;
+            }
+            if let Some(noise) = self.noise {
+                let kraus_operators = noise.kraus_operators();
+                for branch in &mut branches {
+                    for y in 0..self.height {
+                        branch.density =
+                            apply_channel(&branch.density, self.wire_bit(y), &kraus_operators);
+                    }
                 }
             }
+            self.branches.push(branches);
+        }
+        &self.branches[x as usize]
+    }
+
+    // the density matrix averaged over every outcome of the mid-circuit measurements
+    pub fn density_of_column(&mut self, x: i32) -> DMatrix<Complex<f64>> {
+        let size = 2_usize.pow(self.height as u32);
+        self.branches_of_column(x)
+            .iter()
+            .fold(DMatrix::zeros(size, size), |density, branch| {
+                density + &branch.density
+            })
+    }
+
+    // noise and mid-circuit measurements mix the state, so they need the density matrix
+    fn is_mixed(&self, x: i32) -> bool {
+        self.noise.is_some() || !self.is_unitary(x)
+    }
+
+    pub fn partial_state_of_column(
+        &mut self,
+        measure_block_locations: &[(i32, i32)],
+    ) -> DVector<f64> {
+        // measurement blocks that measure in the X or Y basis rotate their wire into the Z basis first
+        let basis_changes: Vec<(i32, Basis)> = measure_block_locations
+            .iter()
+            .filter_map(|&(x, y)| match self.get(x, y)?.gate {
+                Gate::M(basis) if basis != Basis::Z => Some((y, basis)),
+                _ => None,
+            })
+            .collect();
+        if !self.is_mixed(measure_block_locations[0].0)
+            && self.is_clifford(measure_block_locations[0].0)
+        {
+            let mut tableau = Tableau::new(self.height);
+            for (y, &initial_state) in self.initial_states.iter().enumerate() {
+                tableau.prepare(y, initial_state);
+            }
+            for x in 0..measure_block_locations[0].0 + 1 {
+                tableau.apply_column(self, x);
+            }
+            for &(y, basis) in &basis_changes {
+                tableau.rotate_to_z_basis(y as usize, basis);
+            }
+            return tableau.partial_state(measure_block_locations);
+        }
+        let measure_bits: Vec<usize> = measure_block_locations
+            .iter()
+            .map(|&(_, y)| self.wire_bit(y))
+            .collect();
+        let basis_change_bits: Vec<(usize, Matrix2<Complex<f64>>)> = basis_changes
+            .iter()
+            .map(|&(y, basis)| (self.wire_bit(y), basis.change_to_z_basis()))
+            .collect();
+        let probabilities: Vec<f64> = if self.is_mixed(measure_block_locations[0].0) {
+            let mut density = self.density_of_column(measure_block_locations[0].0);
+            for (bit, operator) in &basis_change_bits {
+                apply_operator(&mut density, *bit, operator, 0, 0);
+            }
+            density = density.adjoint();
+            for (bit, operator) in &basis_change_bits {
+                apply_operator(&mut density, *bit, operator, 0, 0);
+            }
+            density.diagonal().iter().map(|amp| amp.re).collect()
         } else {
-            result = result.kronecker(&Matrix2::new(
-                Complex::new(1., 0.),
-                Complex::new(0., 0.),
-                Complex::new(0., 0.),
-                Complex::new(1., 0.),
-            ));
+            let mut state = self.state_of_column(measure_block_locations[0].0).clone();
+            for (bit, operator) in &basis_change_bits {
+                apply_operator(&mut state, *bit, operator, 0, 0);
+            }
+            state.iter().map(|amp| amp.norm_sqr()).collect()
+        };
+        let mut partial_state: DVector<f64> =
+            DVector::zeros(2_usize.pow(measure_block_locations.len() as u32));
+
+        for (idx, probability) in probabilities.iter().enumerate() {
+            let mut partial_index = 0;
+            for (i, &measure_bit) in measure_bits.iter().enumerate() {
+                if idx & measure_bit != 0 {
+                    partial_index |= 1 << i;
+                }
+            }
+            partial_state[partial_index] += probability;
         }
+
+        partial_state
     }
 
-    result
+    // the mask of the measured wires' bits, and a function spreading bit i of a partial index onto the i-th measured wire's bit
+    fn measure_mask_and_spread(
+        &self,
+        measure_block_locations: &[(i32, i32)],
+    ) -> (usize, impl Fn(usize) -> usize) {
+        let measure_bits: Vec<usize> = measure_block_locations
+            .iter()
+            .map(|&(_, y)| self.wire_bit(y))
+            .collect();
+        let measure_mask = measure_bits.iter().fold(0, |mask, bit| mask | bit);
+        let spread = move |partial_index: usize| {
+            measure_bits
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| partial_index & (1 << i) != 0)
+                .fold(0, |idx, (_, bit)| idx | bit)
+        };
+        (measure_mask, spread)
+    }
+
+    // the density matrix of just the measured wires in the computational basis, with the rest traced out
+    pub fn reduced_density_of_column(
+        &mut self,
+        measure_block_locations: &[(i32, i32)],
+    ) -> DMatrix<Complex<f64>> {
+        let (measure_mask, spread) = self.measure_mask_and_spread(measure_block_locations);
+        let density = if self.is_mixed(measure_block_locations[0].0) {
+            self.density_of_column(measure_block_locations[0].0)
+        } else {
+            let state = self.state_of_column(measure_block_locations[0].0);
+            state * state.adjoint()
+        };
+        let size = 2_usize.pow(measure_block_locations.len() as u32);
+        let mut reduced_density = DMatrix::zeros(size, size);
+        for rest in (0..density.nrows()).filter(|rest| rest & measure_mask == 0) {
+            for row in 0..size {
+                for column in 0..size {
+                    reduced_density[(row, column)] +=
+                        density[(rest | spread(row), rest | spread(column))];
+                }
+            }
+        }
+        reduced_density
+    }
+
+    // <target| rho |target> for the reduced density matrix of the measured wires
+    pub fn fidelity_of_column(
+        &mut self,
+        target_state: &DVector<Complex<f64>>,
+        measure_block_locations: &[(i32, i32)],
+    ) -> f64 {
+        (target_state.adjoint()
+            * self.reduced_density_of_column(measure_block_locations)
+            * target_state)[(0, 0)]
+            .re
+    }
+
+    // the operator of columns 0..=x together, applied to every column of the identity
+    pub fn operator_of_columns(&self, x: i32) -> DMatrix<Complex<f64>> {
+        let mut operator = DMatrix::identity(
+            2_usize.pow(self.height as u32),
+            2_usize.pow(self.height as u32),
+        );
+        for column in 0..x + 1 {
+            self.apply_column(column, &mut operator, 0);
+        }
+        operator
+    }
+
+    // true if columns 0..=x make target_operator on the measured wires, up to global phase, with any operator on the other wires
+    // bit i of the target operator's indices is the i-th measured wire, and columns that are not unitary never make an operator
+    pub fn implements_operator(
+        &self,
+        target_operator: &DMatrix<Complex<f64>>,
+        measure_block_locations: &[(i32, i32)],
+    ) -> bool {
+        if !self.is_unitary(measure_block_locations[0].0) {
+            return false;
+        }
+        let (measure_mask, spread) = self.measure_mask_and_spread(measure_block_locations);
+        let mut operator = self.operator_of_columns(measure_block_locations[0].0);
+        // undoing the target leaves the identity on the measured wires if the columns make it
+        let inverse = target_operator.adjoint();
+        for column in 0..operator.ncols() {
+            for rest in (0..operator.nrows()).filter(|rest| rest & measure_mask == 0) {
+                let amplitudes: Vec<Complex<f64>> = (0..inverse.ncols())
+                    .map(|i| operator[(rest | spread(i), column)])
+                    .collect();
+                for row in 0..inverse.nrows() {
+                    operator[(rest | spread(row), column)] = (0..inverse.ncols())
+                        .map(|i| inverse[(row, i)] * amplitudes[i])
+                        .sum();
+                }
+            }
+        }
+        (0..operator.nrows()).all(|row| {
+            (0..operator.ncols()).all(|column| {
+                let expected = if row & measure_mask == column & measure_mask {
+                    operator[(row & !measure_mask, column & !measure_mask)]
+                } else {
+                    Complex::new(0., 0.)
+                };
+                (operator[(row, column)] - expected).norm() < TOLERANCE
+            })
+        })
+    }
+
+    // <P> for the Pauli string measured by the measurement blocks (Z, X, or Y depending on their basis), which is +1 for outcomes of even parity and -1 for odd
+    pub fn expectation_of_column(&mut self, measure_block_locations: &[(i32, i32)]) -> f64 {
+        self.partial_state_of_column(measure_block_locations)
+            .iter()
+            .enumerate()
+            .map(|(index, probability)| {
+                if index.count_ones() % 2 == 0 {
+                    *probability
+                } else {
+                    -probability
+                }
+            })
+            .sum()
+    }
+
+    pub fn satisfies_objective(
+        &mut self,
+        desired_state: DVector<f64>,
+        measure_block_locations: &[(i32, i32)],
+        tolerance: f64,
+    ) -> bool {
+        self.partial_state_of_column(measure_block_locations)
+            .iter()
+            .zip(desired_state.iter())
+            .all(|(a, b)| (a - b).abs() < tolerance)
+    }
 }
 
-pub fn get_state_of_column(
-    block_query: &Query<&Block, Without<Piece>>,
-    control_query: &Query<(&Block, &Control), Without<Piece>>,
-    x: i32,
-) -> DVector<Complex<f64>> {
-    let mut state: DVector<Complex<f64>> = DVector::zeros(2_usize.pow(Y_COUNT as u32));
-    state[0] = Complex::new(1., 0.);
-    for x in 0..x + 1 {
-        state = get_operator_of_column(block_query, control_query, x) * state;
-        // println!("{}", state);
-    }
-    return state;
+// half the summed differences of two distributions over the same outcomes, 0 when they match and 1 when they never overlap
+pub fn total_variation_distance(a: &DVector<f64>, b: &DVector<f64>) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b).abs())
+        .sum::<f64>()
+        / 2.
 }
 
-pub fn get_partial_state_of_column(
-    block_query: &Query<&Block, Without<Piece>>,
-    control_query: &Query<(&Block, &Control), Without<Piece>>,
-    measure_block_locations: Vec<(i32, i32)>,
-) -> DVector<f64> {
-    let state = get_state_of_column(block_query, control_query, measure_block_locations[0].0);
-    let mut partial_state: DVector<f64> =
-        DVector::zeros(2_usize.pow(measure_block_locations.len() as u32));
+// walks from the control at y towards the side it points to, skipping empty cells and other controls, and returns the first operator it reaches
+pub fn control_target(
+    y: i32,
+    on_top: bool,
+    height: i32,
+    gate_at: impl Fn(i32) -> Option<Gate>,
+) -> Option<i32> {
+    let direction = if on_top { -1 } else { 1 };
+    let mut target_y = y + direction;
+    while target_y >= 0 && target_y < height {
+        if gate_at(target_y).is_some_and(|gate| gate.operator().is_some()) {
+            return Some(target_y);
+        }
+        target_y += direction;
+    }
+    None
+}
 
-    for (idx, amp) in state.iter().enumerate() {
-        let mut partial_index = 0;
-        for (i, &(_, y)) in measure_block_locations.iter().enumerate() {
-            if (idx >> (Y_COUNT - 1 - y)) & 1 == 1 {
-                partial_index |= 1 << i;
+// applies a 2x2 operator to the wire at target_bit of every basis state whose control bits match
+pub fn apply_operator<C: Dim, S: StorageMut<Complex<f64>, Dyn, C>>(
+    state: &mut Matrix<Complex<f64>, Dyn, C, S>,
+    target_bit: usize,
+    operator: &Matrix2<Complex<f64>>,
+    control_mask: usize,
+    control_value: usize,
+) {
+    for idx in 0..state.nrows() {
+        if idx & target_bit != 0 || idx & control_mask != control_value {
+            continue;
+        }
+        for column in 0..state.ncols() {
+            let zero = state[(idx, column)];
+            let one = state[(idx | target_bit, column)];
+            state[(idx, column)] = operator[(0, 0)] * zero + operator[(0, 1)] * one;
+            state[(idx | target_bit, column)] = operator[(1, 0)] * zero + operator[(1, 1)] * one;
+        }
+    }
+}
+
+// applies a 4x4 operator in the basis |first second> to the wires at first_bit and second_bit
+pub fn apply_two_qubit_operator<C: Dim, S: StorageMut<Complex<f64>, Dyn, C>>(
+    state: &mut Matrix<Complex<f64>, Dyn, C, S>,
+    first_bit: usize,
+    second_bit: usize,
+    operator: &Matrix4<Complex<f64>>,
+) {
+    for idx in 0..state.nrows() {
+        if idx & (first_bit | second_bit) != 0 {
+            continue;
+        }
+        let indices = [
+            idx,
+            idx | second_bit,
+            idx | first_bit,
+            idx | first_bit | second_bit,
+        ];
+        for column in 0..state.ncols() {
+            let amplitudes = indices.map(|index| state[(index, column)]);
+            for (row, &index) in indices.iter().enumerate() {
+                state[(index, column)] = (0..4).map(|i| operator[(row, i)] * amplitudes[i]).sum();
             }
         }
-        partial_state[partial_index] += amp.norm_sqr();
     }
+}
+
+// projects both sides of rho onto outcome for the wire at target_bit, leaving the probability of outcome as its trace
+pub fn collapse_density(density: &mut DMatrix<Complex<f64>>, target_bit: usize, outcome: bool) {
+    for idx in 0..density.nrows() {
+        if (idx & target_bit != 0) != outcome {
+            density.row_mut(idx).fill(Complex::new(0., 0.));
+            density.column_mut(idx).fill(Complex::new(0., 0.));
+        }
+    }
+}
 
-    partial_state
+// returns the sum of K rho K^dagger over the kraus operators acting on the wire at target_bit
+pub fn apply_channel(
+    density: &DMatrix<Complex<f64>>,
+    target_bit: usize,
+    kraus_operators: &[Matrix2<Complex<f64>>],
+) -> DMatrix<Complex<f64>> {
+    let mut result = DMatrix::zeros(density.nrows(), density.ncols());
+    for kraus_operator in kraus_operators {
+        let mut term = density.clone();
+        apply_operator(&mut term, target_bit, kraus_operator, 0, 0);
+        term = term.adjoint();
+        apply_operator(&mut term, target_bit, kraus_operator, 0, 0);
+        result += term;
+    }
+    result
 }
 
-pub fn satisfies_objective(
-    desired_state: DVector<f64>,
-    block_query: &Query<&Block, Without<Piece>>,
-    control_query: &Query<(&Block, &Control), Without<Piece>>,
-    measure_block_locations: Vec<(i32, i32)>,
-) -> bool {
-    // let state = get_partial_state_of_column(block_query, control_query, measure_block_locations);
-    // println!("{}", state);
-    // state
-    get_partial_state_of_column(block_query, control_query, measure_block_locations)
-        .iter()
-        .zip(desired_state.iter())
-        .all(|(a, b)| (a - b).abs() < TOLERANCE)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Objective;
+
+    fn set(board: &mut Board, x: i32, y: i32, gate: Gate, on_top: bool) {
+        board.set(x, y, Some(Cell { gate, on_top }));
+    }
+
+    fn assert_probabilities(actual: DVector<f64>, expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, b) in actual.iter().zip(expected) {
+            assert!((a - b).abs() < TOLERANCE, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn bell_state() {
+        let mut board = Board::new(3, 2);
+        set(&mut board, 0, 0, Gate::H, false);
+        set(&mut board, 1, 0, Gate::C, false);
+        set(&mut board, 1, 1, Gate::X, false);
+        assert_probabilities(
+            board.partial_state_of_column(&[(2, 0), (2, 1)]),
+            &[0.5, 0., 0., 0.5],
+        );
+    }
+
+    #[test]
+    fn ghz_state() {
+        let mut board = Board::new(4, 3);
+        set(&mut board, 0, 0, Gate::H, false);
+        set(&mut board, 1, 0, Gate::C, false);
+        set(&mut board, 1, 1, Gate::X, false);
+        set(&mut board, 2, 1, Gate::C, false);
+        set(&mut board, 2, 2, Gate::X, false);
+        let mut expected = [0.; 8];
+        expected[0] = 0.5;
+        expected[7] = 0.5;
+        assert_probabilities(
+            board.partial_state_of_column(&[(3, 0), (3, 1), (3, 2)]),
+            &expected,
+        );
+    }
+
+    #[test]
+    fn controlled_gates() {
+        // the control on wire 0 is 0, so only the anticontrolled X on wire 2 flips its target
+        let mut board = Board::new(2, 4);
+        set(&mut board, 0, 0, Gate::C, false);
+        set(&mut board, 0, 1, Gate::X, false);
+        set(&mut board, 0, 2, Gate::X, false);
+        set(&mut board, 0, 3, Gate::AC, true);
+        assert_probabilities(
+            board.partial_state_of_column(&[(1, 1), (1, 2)]),
+            &[0., 0., 1., 0.],
+        );
+        set(&mut board, 0, 3, Gate::C, true);
+        assert_probabilities(
+            board.partial_state_of_column(&[(1, 1), (1, 2)]),
+            &[1., 0., 0., 0.],
+        );
+    }
+
+    #[test]
+    fn controls_skip_measurement_blocks() {
+        // measurement blocks have no operator, so the control has nothing to link to
+        let mut board = Board::new(2, 2);
+        set(&mut board, 0, 0, Gate::H, false);
+        set(&mut board, 1, 0, Gate::C, false);
+        set(&mut board, 1, 1, Gate::M(Basis::Z), false);
+        assert!(board.control_wires(1, 1).is_empty());
+        assert_probabilities(board.partial_state_of_column(&[(1, 1)]), &[1., 0.]);
+    }
+
+    #[test]
+    fn implements_swap() {
+        let swap = Objective::BuildSwap.target_operator().unwrap();
+        let mut board = Board::new(4, 3);
+        set(&mut board, 0, 0, Gate::C, false);
+        set(&mut board, 0, 1, Gate::X, false);
+        assert!(!board.implements_operator(&swap, &[(3, 0), (3, 1)]));
+        set(&mut board, 1, 0, Gate::X, false);
+        set(&mut board, 1, 1, Gate::C, true);
+        set(&mut board, 2, 0, Gate::C, false);
+        set(&mut board, 2, 1, Gate::X, false);
+        assert!(board.implements_operator(&swap, &[(3, 0), (3, 1)]));
+        // SWAP is symmetric, so the order of the measured wires does not matter
+        assert!(board.implements_operator(&swap, &[(3, 1), (3, 0)]));
+        // a reset is not an operator, even on a wire that is already |0>
+        set(&mut board, 0, 2, Gate::Reset, false);
+        assert!(!board.implements_operator(&swap, &[(3, 0), (3, 1)]));
+    }
+
+    #[test]
+    fn collapse_keeps_probability_as_trace() {
+        let plus = InitialState::Plus.amplitudes();
+        let state = dvector![plus.0, plus.1];
+        let mut density = &state * state.adjoint();
+        collapse_density(&mut density, 1, true);
+        assert!((density.trace().re - 0.5).abs() < TOLERANCE);
+        assert!((density[(1, 1)].re - 0.5).abs() < TOLERANCE);
+        assert!(density[(0, 0)].norm() < TOLERANCE && density[(0, 1)].norm() < TOLERANCE);
+    }
+
+    #[test]
+    fn classical_bit_reads_register() {
+        let board = Board::new(1, 3);
+        // wire 0 is the highest bit
+        let register = 0b100;
+        assert!(board.classical_bit(0, register));
+        assert!(!board.classical_bit(1, register));
+        assert!(!board.classical_bit(2, register));
+    }
+
+    #[test]
+    fn mid_circuit_measurement_averages_outcomes() {
+        // measuring half of a Bell pair keeps both outcomes, each still correlated with its partner
+        let mut board = Board::new(4, 2);
+        set(&mut board, 0, 0, Gate::H, false);
+        set(&mut board, 1, 0, Gate::C, false);
+        set(&mut board, 1, 1, Gate::X, false);
+        set(&mut board, 2, 0, Gate::Measure, false);
+        assert_probabilities(
+            board.partial_state_of_column(&[(3, 0), (3, 1)]),
+            &[0.5, 0., 0., 0.5],
+        );
+        assert_eq!(board.branches_of_column(2).len(), 2);
+    }
+
+    #[test]
+    fn classical_controls_follow_each_outcome() {
+        let mut board = Board::new(4, 2);
+        set(&mut board, 0, 0, Gate::H, false);
+        set(&mut board, 1, 0, Gate::Measure, false);
+        set(&mut board, 2, 0, Gate::CC, false);
+        set(&mut board, 2, 1, Gate::X, false);
+        assert_probabilities(
+            board.partial_state_of_column(&[(3, 0), (3, 1)]),
+            &[0.5, 0., 0., 0.5],
+        );
+        // with noise the same branches are followed on the density matrix
+        board.set_noise(Some(Noise::BitFlip(0.)));
+        assert_probabilities(
+            board.partial_state_of_column(&[(3, 0), (3, 1)]),
+            &[0.5, 0., 0., 0.5],
+        );
+    }
+
+    #[test]
+    fn kraus_operators_preserve_trace() {
+        for noise in NOISE_MODES.iter().chain(&[Noise::AmplitudeDamping(0.5)]) {
+            let sum = noise
+                .kraus_operators()
+                .iter()
+                .fold(Matrix2::zeros(), |sum, kraus_operator| {
+                    sum + kraus_operator.adjoint() * kraus_operator
+                });
+            assert!(
+                (sum - Matrix2::identity()).norm() < TOLERANCE,
+                "{}",
+                noise.get_name()
+            );
+        }
+    }
+
+    #[test]
+    fn amplitude_damping_decays_one() {
+        let gamma = 0.1;
+        let kraus_operators = Noise::AmplitudeDamping(gamma).kraus_operators();
+        let mut density = DMatrix::zeros(2, 2);
+        density[(1, 1)] = Complex::new(1., 0.);
+        density = apply_channel(&density, 1, &kraus_operators);
+        assert!((density[(0, 0)].re - gamma).abs() < TOLERANCE);
+        assert!((density[(1, 1)].re - (1. - gamma)).abs() < TOLERANCE);
+        // every column after the X moves another gamma of what is left of |1> to |0>, towards the fixed point |0>
+        let mut board = Board::new(4, 1);
+        board.set_noise(Some(Noise::AmplitudeDamping(gamma)));
+        set(&mut board, 0, 0, Gate::X, false);
+        for x in 0..4 {
+            let probability_of_one = (1. - gamma).powi(x + 1);
+            assert_probabilities(
+                board.partial_state_of_column(&[(x, 0)]),
+                &[1. - probability_of_one, probability_of_one],
+            );
+        }
+    }
+
+    #[test]
+    fn reset_after_x() {
+        let mut board = Board::new(3, 1);
+        set(&mut board, 0, 0, Gate::X, false);
+        set(&mut board, 1, 0, Gate::Reset, false);
+        assert_probabilities(board.partial_state_of_column(&[(2, 0)]), &[1., 0.]);
+    }
+
+    #[test]
+    fn prepare_plus_in_x_basis() {
+        let mut board = Board::new(3, 1);
+        set(&mut board, 0, 0, Gate::X, false);
+        set(&mut board, 1, 0, Gate::PreparePlus, false);
+        set(&mut board, 2, 0, Gate::M(Basis::X), false);
+        assert_probabilities(board.partial_state_of_column(&[(2, 0)]), &[1., 0.]);
+    }
+
+    #[test]
+    fn reset_half_of_bell_pair() {
+        // the partner keeps its even odds, but is no longer correlated with the reset wire
+        let mut board = Board::new(4, 2);
+        set(&mut board, 0, 0, Gate::H, false);
+        set(&mut board, 1, 0, Gate::C, false);
+        set(&mut board, 1, 1, Gate::X, false);
+        assert_probabilities(board.partial_state_of_column(&[(3, 1)]), &[0.5, 0.5]);
+        set(&mut board, 2, 0, Gate::Reset, false);
+        assert_probabilities(board.partial_state_of_column(&[(3, 1)]), &[0.5, 0.5]);
+        assert_probabilities(
+            board.partial_state_of_column(&[(3, 0), (3, 1)]),
+            &[0.5, 0., 0.5, 0.],
+        );
+    }
+
+    #[test]
+    fn partial_state_bit_order() {
+        // bit i of the outcome comes from the i-th location, wherever it is on the board
+        let mut board = Board::new(2, 3);
+        set(&mut board, 0, 2, Gate::X, false);
+        assert_probabilities(
+            board.partial_state_of_column(&[(1, 2), (1, 0)]),
+            &[0., 1., 0., 0.],
+        );
+        assert_probabilities(
+            board.partial_state_of_column(&[(1, 0), (1, 2)]),
+            &[0., 0., 1., 0.],
+        );
+    }
 }
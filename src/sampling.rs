@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use nalgebra::*;
+use rand::prelude::*;
+
+use crate::*;
+
+// count is None when measurements compare exact probabilities, histogram holds the shots of the last sampled measurement
+#[derive(Resource)]
+pub struct Shots {
+    pub count: Option<i32>,
+    pub histogram: Vec<i32>,
+}
+
+#[derive(Component)]
+pub struct HistogramLabel;
+
+impl Shots {
+    // judges objectives that ask for a distribution by drawing shots from it, None for every other objective
+    pub fn sample(
+        &mut self,
+        objective: Objective,
+        board: &mut Board,
+        measure_block_locations: &[(i32, i32)],
+    ) -> Option<bool> {
+        let desired_state = self.draw(objective, board, measure_block_locations)?;
+        Some(passes_chi_squared(&self.histogram, &desired_state))
+    }
+    // the grade of the observed frequencies instead of the exact probabilities, None when sample would be
+    pub fn grade(
+        &mut self,
+        objective: Objective,
+        board: &mut Board,
+        measure_block_locations: &[(i32, i32)],
+    ) -> Option<f64> {
+        let desired_state = self.draw(objective, board, measure_block_locations)?;
+        let shots: i32 = self.histogram.iter().sum();
+        let frequencies = DVector::from_iterator(
+            self.histogram.len(),
+            self.histogram
+                .iter()
+                .map(|&observed| observed as f64 / shots as f64),
+        );
+        Some(1. - total_variation_distance(&frequencies, &desired_state))
+    }
+    // fills the histogram and returns the distribution it should follow
+    fn draw(
+        &mut self,
+        objective: Objective,
+        board: &mut Board,
+        measure_block_locations: &[(i32, i32)],
+    ) -> Option<DVector<f64>> {
+        let count = self.count?;
+        self.histogram.clear();
+        if objective.target_operator().is_some() || objective.target_state().is_some() {
+            return None;
+        }
+        let desired_state = objective.get_desired_state()?;
+        let partial_state = board.partial_state_of_column(measure_block_locations);
+        self.histogram = draw_shots(&partial_state, count, &mut rand::thread_rng());
+        Some(desired_state)
+    }
+}
+
+// counts how often each outcome comes up in count draws from the probabilities
+pub fn draw_shots(probabilities: &DVector<f64>, count: i32, rng: &mut impl Rng) -> Vec<i32> {
+    let mut histogram = vec![0; probabilities.len()];
+    // rounding can leave tiny negative probabilities
+    let total: f64 = probabilities.iter().map(|p| p.max(0.)).sum();
+    for _ in 0..count {
+        let mut remaining = rng.gen::<f64>() * total;
+        let mut outcome = probabilities.len() - 1;
+        for (index, probability) in probabilities.iter().enumerate() {
+            remaining -= probability.max(0.);
+            if remaining < 0. {
+                outcome = index;
+                break;
+            }
+        }
+        histogram[outcome] += 1;
+    }
+    histogram
+}
+
+// Pearson's chi-squared goodness of fit test, where any shot on an outcome the objective rules out fails it
+pub fn passes_chi_squared(histogram: &[i32], desired_state: &DVector<f64>) -> bool {
+    let shots: i32 = histogram.iter().sum();
+    let mut statistic = 0.;
+    let mut possible_outcomes = 0;
+    for (&observed, &probability) in histogram.iter().zip(desired_state.iter()) {
+        if probability <= TOLERANCE {
+            if observed > 0 {
+                return false;
+            }
+            continue;
+        }
+        possible_outcomes += 1;
+        let expected = probability * shots as f64;
+        statistic += (observed as f64 - expected).powi(2) / expected;
+    }
+    if possible_outcomes < 2 {
+        return true;
+    }
+    // Wilson-Hilferty approximation of the chi-squared quantile
+    let degrees = (possible_outcomes - 1) as f64;
+    let spread = 2. / (9. * degrees);
+    let critical_value = degrees * (1. - spread + CHI_SQUARED_Z * spread.sqrt()).powi(3);
+    statistic <= critical_value
+}
+
+pub fn cycle_shots(keys: Res<Input<KeyCode>>, mut shots: ResMut<Shots>) {
+    if keys.just_pressed(CYCLE_SHOTS_KEYCODE) {
+        shots.count = match SHOT_COUNTS
+            .iter()
+            .position(|&count| Some(count) == shots.count)
+        {
+            Some(index) => SHOT_COUNTS.get(index + 1).copied(),
+            None => Some(SHOT_COUNTS[0]),
+        };
+        shots.histogram.clear();
+    }
+}
+
+// outcomes are written from the first measurement block on, like in the objective labels
+pub fn edit_histogram_label(
+    mut histogram_label_query: Query<&mut Text, With<HistogramLabel>>,
+    shots: Res<Shots>,
+) {
+    let Some(count) = shots.count else {
+        for mut text in &mut histogram_label_query {
+            text.sections[0].value = String::new();
+        }
+        return;
+    };
+    let measure_count = shots.histogram.len().trailing_zeros();
+    let mut lines = vec![format!("Shots: {}", count)];
+    for (index, &observed) in shots.histogram.iter().enumerate() {
+        let outcome: String = (0..measure_count)
+            .map(|i| if index & (1 << i) != 0 { '1' } else { '0' })
+            .collect();
+        let bar_length = (observed as f32 / count as f32 * HISTOGRAM_BAR_LENGTH as f32).round();
+        lines.push(format!(
+            "{}: {} {}",
+            outcome,
+            "#".repeat(bar_length as usize),
+            observed
+        ));
+    }
+    for mut text in &mut histogram_label_query {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_shots_follows_probabilities() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let histogram = draw_shots(&dvector![0.25, 0., 0.75], 10000, &mut rng);
+        assert_eq!(histogram.iter().sum::<i32>(), 10000);
+        assert_eq!(histogram[1], 0);
+        assert!((histogram[0] - 2500).abs() < 200, "{:?}", histogram);
+    }
+
+    #[test]
+    fn chi_squared_fails_impossible_outcomes() {
+        assert!(!passes_chi_squared(&[99, 1], &dvector![1., 0.]));
+    }
+
+    #[test]
+    fn chi_squared_passes_single_possible_outcome() {
+        assert!(passes_chi_squared(&[100, 0], &dvector![1., 0.]));
+    }
+
+    #[test]
+    fn chi_squared_at_one_percent() {
+        // the critical value for one degree of freedom is about 6.6
+        let even = dvector![0.5, 0.5];
+        assert!(passes_chi_squared(&[530, 470], &even));
+        assert!(!passes_chi_squared(&[560, 440], &even));
+    }
+
+    #[test]
+    fn shots_draw_every_measurement_outcome() {
+        // the classically controlled X copies a fresh coin flip, so shots land on both 00 and 11
+        let mut board = Board::new(4, 2);
+        for (x, y, gate) in [
+            (0, 0, Gate::H),
+            (1, 0, Gate::Measure),
+            (2, 0, Gate::CC),
+            (2, 1, Gate::X),
+        ] {
+            board.set(
+                x,
+                y,
+                Some(Cell {
+                    gate,
+                    on_top: false,
+                }),
+            );
+        }
+        let mut shots = Shots {
+            count: Some(1000),
+            histogram: vec![],
+        };
+        assert!(shots
+            .sample(Objective::MeasurePhi, &mut board, &[(3, 0), (3, 1)])
+            .is_some());
+        assert!(shots.histogram[0] > 0 && shots.histogram[3] > 0);
+        assert_eq!(shots.histogram[1] + shots.histogram[2], 0);
+    }
+}
@@ -0,0 +1,328 @@
+use nalgebra::*;
+
+use crate::constants::*;
+use crate::quant::*;
+
+// Aaronson-Gottesman tableau, see https://arxiv.org/abs/quant-ph/0406196
+// rows 0..n are destabilizers, rows n..2n are stabilizers, and row 2n is scratch space for measurements
+#[derive(Clone)]
+pub struct Tableau {
+    n: usize,
+    x: Vec<Vec<bool>>,
+    z: Vec<Vec<bool>>,
+    r: Vec<bool>,
+}
+
+impl Tableau {
+    pub fn new(height: i32) -> Self {
+        let n = height as usize;
+        let mut tableau = Tableau {
+            n,
+            x: vec![vec![false; n]; 2 * n + 1],
+            z: vec![vec![false; n]; 2 * n + 1],
+            r: vec![false; 2 * n + 1],
+        };
+        for i in 0..n {
+            tableau.x[i][i] = true;
+            tableau.z[n + i][i] = true;
+        }
+        tableau
+    }
+
+    pub fn h(&mut self, a: usize) {
+        for i in 0..2 * self.n {
+            self.r[i] ^= self.x[i][a] && self.z[i][a];
+            std::mem::swap(&mut self.x[i][a], &mut self.z[i][a]);
+        }
+    }
+
+    pub fn s(&mut self, a: usize) {
+        for i in 0..2 * self.n {
+            self.r[i] ^= self.x[i][a] && self.z[i][a];
+            self.z[i][a] ^= self.x[i][a];
+        }
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) {
+        for i in 0..2 * self.n {
+            self.r[i] ^= self.x[i][control]
+                && self.z[i][target]
+                && (self.x[i][target] == self.z[i][control]);
+            self.x[i][target] ^= self.x[i][control];
+            self.z[i][control] ^= self.z[i][target];
+        }
+    }
+
+    pub fn x(&mut self, a: usize) {
+        self.h(a);
+        self.z(a);
+        self.h(a);
+    }
+
+    pub fn z(&mut self, a: usize) {
+        self.s(a);
+        self.s(a);
+    }
+
+    // Y = iXZ, the global phase is dropped
+    pub fn y(&mut self, a: usize) {
+        self.z(a);
+        self.x(a);
+    }
+
+    // applies gate to target when every control wire is 1 (or 0 for anticontrols)
+    pub fn apply_gate(&mut self, gate: Gate, target: usize, control_wires: &[(usize, bool)]) {
+        let Some(&(control, anti)) = control_wires.first() else {
+            match gate {
+                Gate::X => self.x(target),
+                Gate::Y => self.y(target),
+                Gate::Z => self.z(target),
+                Gate::H => self.h(target),
+                Gate::S => self.s(target),
+                Gate::Sdg => {
+                    self.z(target);
+                    self.s(target);
+                }
+                _ => panic!("{} is not a Clifford gate", gate),
+            }
+            return;
+        };
+        if anti {
+            self.x(control);
+        }
+        match gate {
+            Gate::X => self.cnot(control, target),
+            Gate::Y => {
+                self.z(target);
+                self.s(target);
+                self.cnot(control, target);
+                self.s(target);
+            }
+            Gate::Z => {
+                self.h(target);
+                self.cnot(control, target);
+                self.h(target);
+            }
+            _ => panic!("controlled {} is not a Clifford gate", gate),
+        }
+        if anti {
+            self.x(control);
+        }
+    }
+
+    pub fn prepare(&mut self, a: usize, initial_state: InitialState) {
+        for &gate in initial_state.preparation() {
+            self.apply_gate(gate, a, &[]);
+        }
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.cnot(a, b);
+        self.cnot(b, a);
+        self.cnot(a, b);
+    }
+
+    pub fn cz(&mut self, a: usize, b: usize) {
+        self.h(b);
+        self.cnot(a, b);
+        self.h(b);
+    }
+
+    // iSWAP = (S x S) SWAP CZ
+    pub fn iswap(&mut self, a: usize, b: usize) {
+        self.cz(a, b);
+        self.swap(a, b);
+        self.s(a);
+        self.s(b);
+    }
+
+    pub fn apply_column(&mut self, board: &Board, x: i32) {
+        for y in 0..board.height {
+            if let Some(cell) = board.get(x, y) {
+                if cell.gate.operator().is_some() {
+                    let control_wires: Vec<(usize, bool)> = board
+                        .control_wires(x, y)
+                        .iter()
+                        .map(|&(control_y, anti)| (control_y as usize, anti))
+                        .collect();
+                    self.apply_gate(cell.gate, y as usize, &control_wires);
+                }
+            }
+        }
+        for (y, gate) in board.swap_pairs(x) {
+            match gate {
+                Gate::Swap => self.swap(y as usize, y as usize + 1),
+                Gate::ISwap => self.iswap(y as usize, y as usize + 1),
+                _ => panic!("{:?} is not a Clifford gate", gate),
+            }
+        }
+    }
+
+    fn g(x1: bool, z1: bool, x2: bool, z2: bool) -> i32 {
+        match (x1, z1) {
+            (false, false) => 0,
+            (true, true) => z2 as i32 - x2 as i32,
+            (true, false) => z2 as i32 * (2 * x2 as i32 - 1),
+            (false, true) => x2 as i32 * (1 - 2 * z2 as i32),
+        }
+    }
+
+    // multiplies row i into row h
+    fn rowsum(&mut self, h: usize, i: usize) {
+        let mut sum = 2 * self.r[h] as i32 + 2 * self.r[i] as i32;
+        for j in 0..self.n {
+            sum += Self::g(self.x[i][j], self.z[i][j], self.x[h][j], self.z[h][j]);
+        }
+        self.r[h] = sum.rem_euclid(4) == 2;
+        for j in 0..self.n {
+            self.x[h][j] ^= self.x[i][j];
+            self.z[h][j] ^= self.z[i][j];
+        }
+    }
+
+    // after this, measuring a in the Z basis is the same as measuring it in the given basis before
+    pub fn rotate_to_z_basis(&mut self, a: usize, basis: Basis) {
+        match basis {
+            Basis::Z => {}
+            Basis::X => self.h(a),
+            Basis::Y => {
+                self.z(a);
+                self.s(a);
+                self.h(a);
+            }
+        }
+    }
+
+    pub fn is_random(&self, a: usize) -> bool {
+        (self.n..2 * self.n).any(|p| self.x[p][a])
+    }
+
+    // measures wire a in the computational basis, collapsing to outcome_if_random when the result is not determined
+    pub fn measure(&mut self, a: usize, outcome_if_random: bool) -> bool {
+        if let Some(p) = (self.n..2 * self.n).find(|&p| self.x[p][a]) {
+            for i in 0..2 * self.n {
+                if i != p && self.x[i][a] {
+                    self.rowsum(i, p);
+                }
+            }
+            self.x[p - self.n] = self.x[p].clone();
+            self.z[p - self.n] = self.z[p].clone();
+            self.r[p - self.n] = self.r[p];
+            self.x[p] = vec![false; self.n];
+            self.z[p] = vec![false; self.n];
+            self.z[p][a] = true;
+            self.r[p] = outcome_if_random;
+            outcome_if_random
+        } else {
+            let scratch = 2 * self.n;
+            self.x[scratch] = vec![false; self.n];
+            self.z[scratch] = vec![false; self.n];
+            self.r[scratch] = false;
+            for i in 0..self.n {
+                if self.x[i][a] {
+                    self.rowsum(scratch, i + self.n);
+                }
+            }
+            self.r[scratch]
+        }
+    }
+
+    // same layout as Board::partial_state_of_column, bit i of the index is the outcome of the i-th location
+    pub fn partial_state(&self, measure_block_locations: &[(i32, i32)]) -> DVector<f64> {
+        let mut partial_state: DVector<f64> =
+            DVector::zeros(2_usize.pow(measure_block_locations.len() as u32));
+        for partial_index in 0..partial_state.len() {
+            let mut tableau = self.clone();
+            let mut probability = 1.;
+            for (i, &(_, y)) in measure_block_locations.iter().enumerate() {
+                let outcome = partial_index & (1 << i) != 0;
+                if tableau.is_random(y as usize) {
+                    probability /= 2.;
+                }
+                if tableau.measure(y as usize, outcome) != outcome {
+                    probability = 0.;
+                    break;
+                }
+            }
+            partial_state[partial_index] = probability;
+        }
+        partial_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::*;
+
+    // fills a board with random Clifford gates, controls, and swap pairs, measured on every wire in random bases
+    fn random_board(rng: &mut StdRng) -> (Board, Vec<(i32, i32)>) {
+        let width = rng.gen_range(1..6);
+        let height = rng.gen_range(1..5);
+        let mut board = Board::new(width + 1, height);
+        board.set_initial_states(
+            (0..height)
+                .map(|_| *INITIAL_STATES.choose(rng).unwrap())
+                .collect(),
+        );
+        for x in 0..width {
+            for y in 0..height {
+                let gate = match rng.gen_range(0..10) {
+                    0..=5 => CLIFFORD_GATES[rng.gen_range(0..6)],
+                    6 => Gate::C,
+                    7 => Gate::AC,
+                    8 => Gate::Swap,
+                    _ => continue,
+                };
+                board.set(
+                    x,
+                    y,
+                    Some(Cell {
+                        gate,
+                        on_top: rng.gen(),
+                    }),
+                );
+            }
+        }
+        for y in 0..height {
+            let basis = [Basis::Z, Basis::X, Basis::Y][rng.gen_range(0..3)];
+            board.set(
+                width,
+                y,
+                Some(Cell {
+                    gate: Gate::M(basis),
+                    on_top: false,
+                }),
+            );
+        }
+        let mut measure_block_locations: Vec<(i32, i32)> =
+            (0..height).map(|y| (width, y)).collect();
+        measure_block_locations.shuffle(rng);
+        (board, measure_block_locations)
+    }
+
+    #[test]
+    fn matches_dense_simulation() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut clifford_boards = 0;
+        for _ in 0..500 {
+            let (mut board, measure_block_locations) = random_board(&mut rng);
+            if !board.is_clifford(measure_block_locations[0].0) {
+                continue;
+            }
+            clifford_boards += 1;
+            let stabilizer = board.partial_state_of_column(&measure_block_locations);
+            // noise that never happens still forces the dense density matrix path
+            board.set_noise(Some(Noise::BitFlip(0.)));
+            let dense = board.partial_state_of_column(&measure_block_locations);
+            assert!(
+                (&stabilizer - &dense).amax() < TOLERANCE,
+                "{:?} != {:?}",
+                stabilizer,
+                dense
+            );
+        }
+        assert!(clifford_boards > 100);
+    }
+}
@@ -7,6 +7,17 @@ pub struct Score {
     pub score: i32,
 }
 
+// garbage_columns counts the columns of fixed blocks still to be pushed in from the left by inject_garbage
+#[derive(Resource)]
+pub struct Lives {
+    pub lives: i32,
+    pub garbage_columns: i32,
+}
+
+// the lives a new game starts with, changing it restarts the game
+#[derive(Resource, PartialEq, Eq, Clone, Copy)]
+pub struct StartingLives(pub i32);
+
 #[derive(Component)]
 pub struct Scoreboard;
 
@@ -16,18 +27,58 @@ pub struct LoseScreen;
 pub fn edit_objective_label(
     mut objective_label_query: Query<&mut Text, With<ObjectiveLabel>>,
     objective: Res<Objective>,
+    board: Res<BoardCache>,
+    challenge: Res<Challenge>,
+    mut objective_name: Local<String>,
 ) {
+    // naming a generated objective simulates its circuit, so it is only done when the objective changes
+    if objective.is_changed() {
+        *objective_name = objective.get_name();
+    }
     for mut text in &mut objective_label_query {
-        text.sections[0].value = format!("Current Objective: {}", objective.get_name());
+        let name = if *challenge == Challenge::Endless {
+            format!("Current Objective: {}", *objective_name)
+        } else {
+            format!("{}: {}", challenge.get_name(), *objective_name)
+        };
+        text.sections[0].value = if let Some(noise) = board.0.noise() {
+            format!("{} ({} Noise)", name, noise.get_name())
+        } else {
+            name
+        };
+    }
+}
+
+impl Scoring {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Scoring::Strict => "Strict",
+            Scoring::Graded => "Graded",
+        }
     }
 }
 
 pub fn edit_scoreboard(
     mut scoreboard_query: Query<&mut Text, With<Scoreboard>>,
     score: Res<Score>,
+    difficulty: Res<Difficulty>,
+    scoring: Res<Scoring>,
+    lives: Res<Lives>,
+    inputs: Res<Inputs>,
 ) {
+    let level = DIFFICULTIES
+        .iter()
+        .position(|&other| other == *difficulty)
+        .map_or(0, |index| index + 1);
     for mut text in &mut scoreboard_query {
-        text.sections[0].value = format!("Score: {}", score.score);
+        text.sections[0].value = format!(
+            "Score: {}\nLives: {}\nDifficulty: {}\nScoring: {}\nInputs: {}",
+            score.score,
+            lives.lives,
+            level,
+            scoring.get_name(),
+            inputs.get_name()
+        );
     }
 }
 
@@ -59,6 +110,7 @@ pub fn show_lose_screen(mut commands: Commands) {
     ));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn check_game_restart(
     mut commands: Commands,
     keys: Res<Input<KeyCode>>,
@@ -67,8 +119,17 @@ pub fn check_game_restart(
     lose_screen: Query<Entity, With<LoseScreen>>,
     mut score: ResMut<Score>,
     mut piece_info: ResMut<PieceInfo>,
+    mut objective: ResMut<Objective>,
+    challenge: Res<Challenge>,
+    mut round: ResMut<ChallengeRound>,
+    mut shots: ResMut<Shots>,
+    mut lives: ResMut<Lives>,
+    starting_lives: Res<StartingLives>,
+    inputs: Res<Inputs>,
+    mut board: ResMut<BoardCache>,
 ) {
-    if keys.just_pressed(KeyCode::R) {
+    // switching challenges or starting lives restarts the game as well
+    if keys.just_pressed(KeyCode::R) || challenge.is_changed() || starting_lives.is_changed() {
         for entity in &blocks {
             commands.entity(entity).despawn_recursive();
         }
@@ -76,7 +137,62 @@ pub fn check_game_restart(
             commands.entity(entity).despawn_recursive();
         }
         score.score = 0;
+        lives.lives = starting_lives.0;
+        lives.garbage_columns = 0;
+        board.0.set_initial_states(inputs.pick(*challenge));
         piece_info.pieces_since_objective = 0;
+        // challenges replace this with their own objective when their next round starts
+        *objective = Objective::Measure0;
+        round.started = false;
+        shots.histogram.clear();
         next_state.set(GameState::Playing);
     }
 }
+
+pub fn cycle_noise(keys: Res<Input<KeyCode>>, mut board: ResMut<BoardCache>) {
+    if keys.just_pressed(CYCLE_NOISE_KEYCODE) {
+        let next_noise = match NOISE_MODES
+            .iter()
+            .position(|&noise| Some(noise) == board.0.noise())
+        {
+            Some(index) => NOISE_MODES.get(index + 1).copied(),
+            None => Some(NOISE_MODES[0]),
+        };
+        board.0.set_noise(next_noise);
+    }
+}
+
+pub fn cycle_scoring(keys: Res<Input<KeyCode>>, mut scoring: ResMut<Scoring>) {
+    if keys.just_pressed(CYCLE_SCORING_KEYCODE) {
+        *scoring = match *scoring {
+            Scoring::Strict => Scoring::Graded,
+            Scoring::Graded => Scoring::Strict,
+        };
+    }
+}
+
+pub fn cycle_starting_lives(keys: Res<Input<KeyCode>>, mut starting_lives: ResMut<StartingLives>) {
+    if keys.just_pressed(CYCLE_LIVES_KEYCODE) {
+        *starting_lives = match STARTING_LIVES
+            .iter()
+            .position(|&other| other == *starting_lives)
+        {
+            Some(index) => STARTING_LIVES
+                .get(index + 1)
+                .copied()
+                .unwrap_or(STARTING_LIVES[0]),
+            None => STARTING_LIVES[0],
+        };
+    }
+}
+
+// takes a life and loses the game when none are left, returning whether the game goes on
+pub fn lose_life(lives: &mut Lives, next_state: &mut NextState<GameState>) -> bool {
+    lives.lives -= 1;
+    if lives.lives > 0 {
+        true
+    } else {
+        next_state.set(GameState::Lost);
+        false
+    }
+}